@@ -1,135 +1,589 @@
 use crate::{
-    error::{ConfigError, JsonType},
-    processors::{EventPatterns, PatternColor, StateProcessor},
+    error::{ConfigError, Location},
+    processors::{EventPatterns, EventTimeout, FieldCounter, PatternColor, StateProcessor},
+    formats::{ContainerFormat, InputFormat, Template},
+    grok::Grok,
+    record::RecordAssembler,
+    timestamp::{TimestampFormat, TimestampParser},
 };
 use ansi_term::Color;
 use regex::Regex;
-use serde_json;
-use std::{fs::File, io::BufReader};
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{self, Display},
+    fs, mem,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 type Result<T> = std::result::Result<T, ConfigError>;
 
 #[derive(Debug)]
 pub struct Config {
+    pub prefix: Option<String>,
+    pub timestamp: Option<TimestampParser>,
+    pub multiline: Option<RecordAssembler>,
+    pub input_format: InputFormat,
+    pub template: Option<Template>,
+    pub container: Option<ContainerFormat>,
     pub pattern_colors: Option<Vec<PatternColor>>,
     pub events: Vec<EventPatterns>,
     pub states: Vec<StateProcessor>,
+    pub field_counts: Vec<FieldCounter>,
+}
+
+/// The file formats of config files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            format => Err(format!("Unknown config format: {}", format)),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Selects the format by the extension of the file, JSON is the default
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| extension.to_str()?.to_lowercase().parse().ok())
+            .unwrap_or(ConfigFormat::Json)
+    }
+}
+
+/// The schema of config files. Values that don't depend on other keys are parsed while the file is read, so their
+/// errors have a location. Patterns are compiled afterwards, since they depend on the prefix and the grok patterns.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(deserialize_with = "deserialize_one_or_more")]
+    extends: Vec<PathBuf>,
+    prefix: Option<String>,
+    timestamp: Option<TimestampConfig>,
+    multiline: Option<MultilineConfig>,
+    #[serde(deserialize_with = "deserialize_optional_parsed")]
+    input_format: Option<InputFormat>,
+    #[serde(deserialize_with = "deserialize_optional_parsed")]
+    template: Option<Template>,
+    #[serde(deserialize_with = "deserialize_optional_parsed")]
+    container: Option<ContainerFormat>,
+    grok_patterns: HashMap<String, String>,
+    pattern_colors: Option<Vec<PatternColorConfig>>,
+    event_patterns: Vec<EventPatternsConfig>,
+    state_patterns: Vec<StatePatternConfig>,
+    field_counts: Vec<FieldCountConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimestampConfig {
+    #[serde(deserialize_with = "deserialize_parsed")]
+    format: TimestampFormat,
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(default)]
+    field: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MultilineConfig {
+    #[serde(default)]
+    start_pattern: Option<String>,
+    #[serde(default)]
+    continuation_pattern: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternColorConfig {
+    #[serde(default)]
+    field: Option<String>,
+    pattern: String,
+    #[serde(deserialize_with = "deserialize_color")]
+    color: Color,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventPatternsConfig {
+    #[serde(default)]
+    field: Option<String>,
+    #[serde(default)]
+    key: Option<String>,
+    #[serde(default)]
+    report_orphans: bool,
+    #[serde(default, deserialize_with = "deserialize_optional_timeout")]
+    timeout: Option<EventTimeout>,
+    #[serde(default)]
+    max_lines: Option<usize>,
+    /// Events that are only recognized inside this event
+    #[serde(default)]
+    children: Vec<EventPatternsConfig>,
+    start_pattern: String,
+    end_pattern: String,
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    color: Option<Color>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatePatternConfig {
+    #[serde(default)]
+    field: Option<String>,
+    pattern: String,
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    color: Option<Color>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldCountConfig {
+    field: String,
+}
+
+impl ConfigFile {
+    /// Merges the config on top of a config it extends. Its values override the values of the base. Entries of the
+    /// lists override the entries of the base that match the same patterns, other entries are added after them.
+    /// Patterns are compiled after the merge, so the patterns of the base get the prefix of this config if it has one,
+    /// not the prefix of the base.
+    fn merge_into(self, base: &mut ConfigFile) {
+        base.prefix = self.prefix.or_else(|| base.prefix.take());
+        base.timestamp = self.timestamp.or_else(|| base.timestamp.take());
+        base.multiline = self.multiline.or_else(|| base.multiline.take());
+        base.input_format = self.input_format.or_else(|| base.input_format.take());
+        base.template = self.template.or_else(|| base.template.take());
+        base.container = self.container.or(base.container);
+        base.grok_patterns.extend(self.grok_patterns);
+
+        if let Some(pattern_colors) = self.pattern_colors {
+            merge_entries(
+                base.pattern_colors.get_or_insert_with(Vec::new),
+                pattern_colors,
+                |a, b| a.field == b.field && a.pattern == b.pattern,
+            );
+        }
+        merge_entries(&mut base.event_patterns, self.event_patterns, |a, b| {
+            a.field == b.field && a.start_pattern == b.start_pattern && a.end_pattern == b.end_pattern
+        });
+        merge_entries(&mut base.state_patterns, self.state_patterns, |a, b| {
+            a.field == b.field && a.pattern == b.pattern
+        });
+        merge_entries(&mut base.field_counts, self.field_counts, |a, b| a.field == b.field);
+    }
+}
+
+fn merge_entries<T, F: Fn(&T, &T) -> bool>(base: &mut Vec<T>, entries: Vec<T>, is_same: F) {
+    for entry in entries {
+        match base.iter_mut().find(|base_entry| is_same(base_entry, &entry)) {
+            Some(base_entry) => *base_entry = entry,
+            None => base.push(entry),
+        }
+    }
+}
+
+/// Reads a config file and the files it extends. `stack` holds the files that are being read to detect cycles.
+fn read_config_file(path: &Path, format: ConfigFormat, stack: &mut Vec<PathBuf>) -> Result<ConfigFile> {
+    let canonical_path = path.canonicalize().map_err(|err| ConfigError::Io(path.to_owned(), err))?;
+    if stack.contains(&canonical_path) {
+        let mut cycle = stack.clone();
+        cycle.push(canonical_path);
+        return Err(ConfigError::IncludeCycle(cycle));
+    }
+
+    let content = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_owned(), err))?;
+    let config_file = parse_config_file(&content, format)?;
+    stack.push(canonical_path);
+    let config_file = resolve_extends(config_file, path.parent().unwrap_or_else(|| Path::new("")), stack);
+    stack.pop();
+    config_file
+}
+
+/// Merges the config on top of the files it extends, which are relative to `directory`
+fn resolve_extends(mut config_file: ConfigFile, directory: &Path, stack: &mut Vec<PathBuf>) -> Result<ConfigFile> {
+    let mut merged = ConfigFile::default();
+    for path in mem::take(&mut config_file.extends) {
+        let path = directory.join(path);
+        let base = read_config_file(&path, ConfigFormat::from_path(&path), stack).map_err(|err| match err {
+            ConfigError::Io(_, _) | ConfigError::Include(_, _) | ConfigError::IncludeCycle(_) => err,
+            err => ConfigError::Include(path, Box::new(err)),
+        })?;
+        base.merge_into(&mut merged);
+    }
+    config_file.merge_into(&mut merged);
+    Ok(merged)
+}
+
+fn parse_config_file(content: &str, format: ConfigFormat) -> Result<ConfigFile> {
+    match format {
+        ConfigFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(content);
+            let config_file = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+                let path = err.path().to_string();
+                let err = err.into_inner();
+                match err.classify() {
+                    serde_json::error::Category::Data => {
+                        ConfigError::Schema(path, Location::of_json_error(&err), err.to_string())
+                    }
+                    _ => ConfigError::JsonParse(err),
+                }
+            })?;
+            deserializer.end()?;
+            Ok(config_file)
+        }
+        ConfigFormat::Yaml => {
+            serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(content)).map_err(|err| {
+                let path = err.path().to_string();
+                let err = err.into_inner();
+                // Syntax errors are not in any value
+                if path == "." {
+                    ConfigError::YamlParse(err)
+                } else {
+                    // serde_yaml prepends the path to the message
+                    let message = err.to_string();
+                    let message = message.strip_prefix(&format!("{}: ", path)).unwrap_or(&message).to_owned();
+                    ConfigError::Schema(path, Location::of_yaml_error(&err), message)
+                }
+            })
+        }
+        ConfigFormat::Toml => {
+            serde_path_to_error::deserialize(&mut toml::Deserializer::new(content)).map_err(|err| {
+                let path = err.path().to_string();
+                let err = err.into_inner();
+                if path == "." {
+                    ConfigError::TomlParse(err)
+                } else {
+                    // The toml crate appends the key and the location to the message
+                    let mut message = err.to_string();
+                    if let Some(end) = message.rfind(" for key `") {
+                        message.truncate(end);
+                    }
+                    ConfigError::Schema(path, Location::of_toml_error(&err), message)
+                }
+            })
+        }
+    }
+}
+
+fn deserialize_parsed<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+}
+
+/// Accepts a single value in place of a list
+fn deserialize_one_or_more<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMore<T> {
+        One(T),
+        More(Vec<T>),
+    }
+
+    Ok(match OneOrMore::deserialize(deserializer)? {
+        OneOrMore::One(value) => vec![value],
+        OneOrMore::More(values) => values,
+    })
+}
+
+fn deserialize_optional_parsed<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+/// Colors are fixed color numbers. They can be strings as well, e.g. `"28"`, which JSON config files use.
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Color, D::Error> {
+    deserializer.deserialize_any(ColorVisitor)
+}
+
+fn deserialize_optional_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Color>, D::Error> {
+    #[derive(Deserialize)]
+    struct OptionalColor(#[serde(deserialize_with = "deserialize_color")] Color);
+
+    Ok(Option::<OptionalColor>::deserialize(deserializer)?.map(|color| color.0))
+}
+
+/// Compiles the patterns of an event and of its children. `name` is the path of the event in the config file.
+fn event_patterns_from_config<F>(
+    event_pattern: EventPatternsConfig,
+    name: &str,
+    prefix: Option<&str>,
+    regex_with_prefix: &F,
+) -> Result<EventPatterns>
+where
+    F: Fn(&Option<&str>, &str, &str) -> Result<Regex>,
+{
+    // Patterns matching a field don't get the prefix
+    let pattern_prefix = if event_pattern.field.is_some() { None } else { prefix };
+    let children = event_pattern
+        .children
+        .into_iter()
+        .enumerate()
+        .map(|(index, child)| {
+            event_patterns_from_config(child, &format!("{}.children[{}]", name, index), prefix, regex_with_prefix)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let event_patterns = EventPatterns::new(
+        regex_with_prefix(&pattern_prefix, &format!("{}.start_pattern", name), &event_pattern.start_pattern)?,
+        regex_with_prefix(&pattern_prefix, &format!("{}.end_pattern", name), &event_pattern.end_pattern)?,
+    )
+    .with_color(event_pattern.color)
+    .with_field(event_pattern.field)
+    .with_key(event_pattern.key)
+    .with_report_orphans(event_pattern.report_orphans)
+    .with_timeout(event_pattern.timeout)
+    .with_max_lines(event_pattern.max_lines)
+    .with_children(children);
+    if !event_patterns.has_key_captures() {
+        return Err(ConfigError::InvalidValue(
+            format!("{}.key", name),
+            "The start and end patterns must capture the key".to_owned(),
+        ));
+    }
+    if event_patterns.max_lines.is_some_and(|max_lines| max_lines < 2) {
+        return Err(ConfigError::InvalidValue(
+            format!("{}.max_lines", name),
+            "Events have at least a start and an end line".to_owned(),
+        ));
+    }
+    Ok(event_patterns)
+}
+
+/// Timeouts are a number of lines or a duration in a string, e.g. `100` or `"30s"`
+fn deserialize_optional_timeout<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<EventTimeout>, D::Error> {
+    #[derive(Deserialize)]
+    struct OptionalTimeout(#[serde(deserialize_with = "deserialize_timeout")] EventTimeout);
+
+    fn deserialize_timeout<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<EventTimeout, D::Error> {
+        deserializer.deserialize_any(TimeoutVisitor)
+    }
+
+    Ok(Option::<OptionalTimeout>::deserialize(deserializer)?.map(|timeout| timeout.0))
+}
+
+struct TimeoutVisitor;
+
+impl<'de> de::Visitor<'de> for TimeoutVisitor {
+    type Value = EventTimeout;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number of lines or a duration, e.g. \"30s\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<EventTimeout, E> {
+        usize::try_from(value)
+            .map(EventTimeout::Lines)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<EventTimeout, E> {
+        usize::try_from(value)
+            .map(EventTimeout::Lines)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<EventTimeout, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> de::Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a color number from 0 to 255")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<Color, E> {
+        u8::try_from(value)
+            .map(Color::Fixed)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<Color, E> {
+        u8::try_from(value)
+            .map(Color::Fixed)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Color, E> {
+        value
+            .parse()
+            .map(Color::Fixed)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
 }
 
 impl Config {
-    pub fn from_json_file(file: File) -> Result<Self> {
-        Self::from_json_value(serde_json::from_reader(BufReader::new(file))?)
+    /// Reads a config file. The files it extends are read in the format of their extension.
+    pub fn from_path(path: &Path, format: ConfigFormat) -> Result<Self> {
+        Self::from_config_file(read_config_file(path, format, &mut Vec::new())?)
+    }
+
+    /// Parses a config. The files it extends are relative to the working directory.
+    #[cfg(test)]
+    pub fn parse(content: &str, format: ConfigFormat) -> Result<Self> {
+        let config_file = resolve_extends(parse_config_file(content, format)?, Path::new(""), &mut Vec::new())?;
+        Self::from_config_file(config_file)
     }
 
     #[cfg(test)]
     pub fn from_json_str<T: AsRef<str>>(json_str: T) -> Result<Self> {
-        Self::from_json_value(serde_json::from_str(json_str.as_ref())?)
+        Self::parse(json_str.as_ref(), ConfigFormat::Json)
     }
 
-    fn from_json_value(json_value: serde_json::Value) -> Result<Self> {
-        use serde_json::Value;
+    fn from_config_file(config_file: ConfigFile) -> Result<Self> {
+        let grok = Grok::default().with_patterns(config_file.grok_patterns);
+        let expand = |name: &str, pattern: &str| {
+            grok.expand(pattern)
+                .map_err(|err| ConfigError::Grok(name.to_owned(), err))
+        };
+        let regex_with_prefix = |prefix: &Option<&str>, name: &str, pattern: &str| {
+            create_regex_with_prefix(prefix, &expand(name, pattern)?)
+                .map_err(|err| ConfigError::Regex(name.to_owned(), err))
+        };
+
+        let prefix = config_file
+            .prefix
+            .map(|prefix| expand("prefix", &prefix))
+            .transpose()?;
 
-        let prefix = match &json_value["prefix"] {
-            Value::String(prefix) => Some(prefix),
-            Value::Null => None,
-            _ => return Err(ConfigError::JsonType("prefix", JsonType::String)),
+        let timestamp = match config_file.timestamp {
+            Some(timestamp) => {
+                let regex = match timestamp.pattern {
+                    Some(pattern) => Some(
+                        Regex::new(&expand("timestamp.pattern", &pattern)?)
+                            .map_err(|err| ConfigError::Regex("timestamp.pattern".to_owned(), err))?,
+                    ),
+                    None => None,
+                };
+                Some(TimestampParser::new(timestamp.format, regex).with_field(timestamp.field))
+            }
+            None => None,
         };
 
-        let pattern_colors = match &json_value["pattern_colors"] {
-            Value::Array(pattern_colors) => Some(pattern_colors
-                    .iter()
-                    .map(|pattern_color| {
-                        let regex = match &pattern_color["pattern"] {
-                            Value::String(pattern) => {
-                                create_regex_with_prefix(&prefix.map(|prefix| prefix.as_str()), pattern)
-                                    .map_err(|err| ConfigError::Regex("pattern_colors.pattern", err))?
-                            }
-                            _ => return Err(ConfigError::JsonType("pattern_colors.pattern", JsonType::String)),
-                        };
-                        let color = match &pattern_color["color"] {
-                            Value::String(fixed_color) => Color::Fixed(
-                                fixed_color
-                                    .parse()
-                                    .map_err(|err| ConfigError::ParseInt("pattern_colors.color", err))?,
-                            ),
-                            _ => return Err(ConfigError::JsonType("pattern_colors.color", JsonType::String)),
-                        };
-                        Ok(PatternColor { regex, color })
-                    })
-                    .collect::<Result<Vec<_>>>()?),
-            Value::Null => None,
-            _ => return Err(ConfigError::JsonType("pattern_colors", JsonType::Array)),
+        let multiline = match config_file.multiline {
+            Some(multiline) => {
+                let start_pattern = match multiline.start_pattern {
+                    Some(pattern) => Some(expand("multiline.start_pattern", &pattern)?),
+                    None => prefix.clone(),
+                };
+                let continuation_pattern = match multiline.continuation_pattern {
+                    Some(pattern) => Some(expand("multiline.continuation_pattern", &pattern)?),
+                    None => None,
+                };
+                if start_pattern.is_none() && continuation_pattern.is_none() {
+                    return Err(ConfigError::InvalidValue(
+                        "multiline".to_owned(),
+                        "A start pattern, a prefix or a continuation pattern is required".to_owned(),
+                    ));
+                }
+                Some(
+                    RecordAssembler::from_patterns(start_pattern.as_deref(), continuation_pattern.as_deref())
+                        .map_err(|err| ConfigError::Regex("multiline".to_owned(), err))?,
+                )
+            }
+            None => None,
         };
 
-        let events = match &json_value["event_patterns"] {
-            Value::Array(event_patterns) => event_patterns
-                .iter()
-                .map(|event_pattern| {
-                    let start_regex = match &event_pattern["start_pattern"] {
-                        Value::String(pattern) => {
-                            create_regex_with_prefix(&prefix.map(|prefix| prefix.as_str()), pattern)
-                                .map_err(|err| ConfigError::Regex("event_patterns.start_pattern", err))?
-                        }
-                        _ => return Err(ConfigError::JsonType("event_patterns.start_pattern", JsonType::String)),
-                    };
-                    let end_regex = match &event_pattern["end_pattern"] {
-                        Value::String(pattern) => {
-                            create_regex_with_prefix(&prefix.map(|prefix| prefix.as_str()), pattern)
-                                .map_err(|err| ConfigError::Regex("event_patterns.end_pattern", err))?
-                        }
-                        _ => return Err(ConfigError::JsonType("event_patterns.end_pattern", JsonType::String)),
-                    };
-                    let color = match &event_pattern["color"] {
-                        Value::String(fixed_color) => Some(Color::Fixed(
-                            fixed_color
-                                .parse()
-                                .map_err(|err| ConfigError::ParseInt("event_patterns.color", err))?,
-                        )),
-                        Value::Null => None,
-                        _ => return Err(ConfigError::JsonType("event_patterns.color", JsonType::String)),
-                    };
-                    Ok(EventPatterns {
-                        start_regex,
-                        end_regex,
-                        color,
+        // Patterns matching a field don't get the prefix
+        let prefix_for = |field: &Option<String>| if field.is_some() { None } else { prefix.as_deref() };
+
+        let pattern_colors = match config_file.pattern_colors {
+            Some(pattern_colors) => Some(
+                pattern_colors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, pattern_color)| {
+                        let name = format!("pattern_colors[{}].pattern", index);
+                        Ok(PatternColor {
+                            regex: regex_with_prefix(&prefix_for(&pattern_color.field), &name, &pattern_color.pattern)?,
+                            color: pattern_color.color,
+                            field: pattern_color.field,
+                        })
                     })
-                })
-                .collect::<Result<Vec<_>>>()?,
-            Value::Null => Default::default(),
-            _ => return Err(ConfigError::JsonType("event_patterns", JsonType::Array)),
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            None => None,
         };
 
-        let states = match &json_value["state_patterns"] {
-            Value::Array(state_patterns) => state_patterns
-                .iter()
-                .map(|state_pattern| {
-                    let regex = match &state_pattern["pattern"] {
-                        Value::String(pattern) => {
-                            create_regex_with_prefix(&prefix.map(|prefix| prefix.as_str()), pattern)
-                                .map_err(|err| ConfigError::Regex("state_patterns.pattern", err))?
-                        }
-                        _ => return Err(ConfigError::JsonType("state_patterns.pattern", JsonType::String)),
-                    };
-                    let color = match &state_pattern["color"] {
-                        Value::String(fixed_color) => Some(Color::Fixed(
-                            fixed_color
-                                .parse()
-                                .map_err(|err| ConfigError::ParseInt("state_patterns.color", err))?,
-                        )),
-                        Value::Null => None,
-                        _ => return Err(ConfigError::JsonType("state_patterns.color", JsonType::String)),
-                    };
-                    Ok(StateProcessor::new(regex, color))
-                })
-                .collect::<Result<Vec<_>>>()?,
-            Value::Null => Default::default(),
-            _ => return Err(ConfigError::JsonType("state_patterns", JsonType::Array)),
-        };
+        let events = config_file
+            .event_patterns
+            .into_iter()
+            .enumerate()
+            .map(|(index, event_pattern)| {
+                event_patterns_from_config(
+                    event_pattern,
+                    &format!("event_patterns[{}]", index),
+                    prefix.as_deref(),
+                    &regex_with_prefix,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let states = config_file
+            .state_patterns
+            .into_iter()
+            .enumerate()
+            .map(|(index, state_pattern)| {
+                let regex = regex_with_prefix(
+                    &prefix_for(&state_pattern.field),
+                    &format!("state_patterns[{}].pattern", index),
+                    &state_pattern.pattern,
+                )?;
+                Ok(StateProcessor::new(regex, state_pattern.color).with_field(state_pattern.field))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let field_counts = config_file
+            .field_counts
+            .into_iter()
+            .map(|field_count| FieldCounter::new(field_count.field))
+            .collect();
 
         Ok(Self {
+            prefix,
+            timestamp,
+            multiline,
+            input_format: config_file.input_format.unwrap_or_default(),
+            template: config_file.template,
+            container: config_file.container,
             pattern_colors,
             events,
             states,
+            field_counts,
         })
     }
 }
@@ -143,8 +597,15 @@ pub(crate) fn create_regex_with_prefix(
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{Config, ConfigFormat};
+    use crate::{
+        error::{ConfigError, Location},
+        formats::{ContainerFormat, InputFormat},
+        processors::EventTimeout,
+        timestamp::TimestampFormat,
+    };
     use ansi_term::Color;
+    use std::path::{Path, PathBuf};
 
     #[test]
     pub fn test_config_from_json() {
@@ -167,6 +628,7 @@ mod tests {
         }"#;
 
         let config = Config::from_json_str(json).unwrap();
+        assert_eq!(config.prefix.as_deref(), Some(prefix));
         let pattern = &config.pattern_colors.unwrap()[0];
         assert_eq!(pattern.regex.as_str(), format!(r#"{}INFO"#, prefix));
         assert_eq!(pattern.color, Color::Fixed(28));
@@ -187,13 +649,361 @@ mod tests {
         assert_eq!(states[0].color, Some(Color::Fixed(30)));
     }
 
+    #[test]
+    pub fn test_timestamp_config() {
+        let json = r#"{
+            "timestamp": { "format": "%d/%m/%Y:%H:%M:%S %z", "pattern": "\\[([^\\]]+)\\]" }
+        }"#;
+
+        let config = Config::from_json_str(json).unwrap();
+        let timestamp = config.timestamp.unwrap();
+        assert_eq!(timestamp.format, TimestampFormat::Custom("%d/%m/%Y:%H:%M:%S %z".to_owned()));
+        assert_eq!(timestamp.regex.unwrap().as_str(), r"\[([^\]]+)\]");
+
+        let json = r#"{ "timestamp": { "format": "iso8601" } }"#;
+        let timestamp = Config::from_json_str(json).unwrap().timestamp.unwrap();
+        assert_eq!(timestamp.format, TimestampFormat::Iso8601);
+        assert!(timestamp.regex.is_none());
+
+        let json = r#"{ "timestamp": { "format": "unknown" } }"#;
+        assert!(Config::from_json_str(json).is_err());
+    }
+
+    #[test]
+    pub fn test_multiline_config() {
+        let json = r#"{
+            "prefix": "[\\d]{4}-[\\d]{2}-[\\d]{2} [\\d]{2}:[\\d]{2}:[\\d]{2} ",
+            "multiline": { "continuation_pattern": "^\\tat " }
+        }"#;
+
+        let mut multiline = Config::from_json_str(json).unwrap().multiline.unwrap();
+        assert_eq!(multiline.push_line("2020-01-01 10:00:50 ERROR Failed to start application"), None);
+        assert_eq!(multiline.push_line("An unknown error occurred"), None);
+        assert_eq!(multiline.push_line("\tat Main.main(Main.java:1)"), None);
+        assert_eq!(
+            multiline.flush().as_deref(),
+            Some(concat!(
+                "2020-01-01 10:00:50 ERROR Failed to start application\n",
+                "An unknown error occurred\n",
+                "\tat Main.main(Main.java:1)"
+            ))
+        );
+
+        let json = r#"{ "multiline": {} }"#;
+        assert!(Config::from_json_str(json).is_err());
+    }
+
+    #[test]
+    pub fn test_field_patterns() {
+        let json = r#"{
+            "prefix": "(?P<level>\\w+) ",
+            "pattern_colors": [
+                { "field": "level", "pattern": "^ERROR$", "color": "88" }
+            ],
+            "event_patterns": [
+                { "start_pattern": "Mouse left down", "end_pattern": "Mouse left up" }
+            ],
+            "state_patterns": [
+                { "field": "state", "pattern": ".", "color": "30" }
+            ]
+        }"#;
+
+        let config = Config::from_json_str(json).unwrap();
+        let pattern = &config.pattern_colors.unwrap()[0];
+        assert_eq!(pattern.regex.as_str(), "^ERROR$");
+        assert_eq!(pattern.field.as_deref(), Some("level"));
+        assert_eq!(config.events[0].start_regex.as_str(), r"(?P<level>\w+) Mouse left down");
+        assert_eq!(config.events[0].field, None);
+        assert_eq!(config.states[0].regex.as_str(), ".");
+        assert_eq!(config.states[0].field.as_deref(), Some("state"));
+    }
+
+    #[test]
+    pub fn test_json_input_config() {
+        let json = r#"{
+            "input_format": "json",
+            "template": "{ts} {level} {msg}",
+            "timestamp": { "format": "rfc3339", "field": "ts" },
+            "pattern_colors": [
+                { "field": "level", "pattern": "^ERROR$", "color": "88" }
+            ]
+        }"#;
+
+        let config = Config::from_json_str(json).unwrap();
+        assert_eq!(config.input_format, InputFormat::Json);
+        assert_eq!(config.template, Some("{ts} {level} {msg}".parse().unwrap()));
+        assert_eq!(config.timestamp.unwrap().field.as_deref(), Some("ts"));
+
+        let json = r#"{ "input_format": "xml" }"#;
+        assert!(Config::from_json_str(json).is_err());
+
+        let json = r#"{ "container": "cri", "input_format": "json" }"#;
+        assert_eq!(Config::from_json_str(json).unwrap().container, Some(ContainerFormat::Cri));
+
+        let json = r#"{ "container": "podman" }"#;
+        assert!(Config::from_json_str(json).is_err());
+    }
+
+    #[test]
+    pub fn test_access_log_config() {
+        let json = r#"{
+            "input_format": "combined",
+            "timestamp": { "format": "%d/%b/%Y:%H:%M:%S %z", "field": "time_local" },
+            "pattern_colors": [
+                { "field": "status", "pattern": "^5", "color": "88" }
+            ],
+            "field_counts": [
+                { "field": "status" },
+                { "field": "path" }
+            ]
+        }"#;
+
+        let config = Config::from_json_str(json).unwrap();
+        assert_eq!(config.input_format, "combined".parse().unwrap());
+        assert_eq!(config.field_counts.len(), 2);
+
+        let json = r#"{ "input_format": "$remote_addr $status" }"#;
+        assert!(Config::from_json_str(json).is_ok());
+
+        let json = r#"{ "field_counts": ["status"] }"#;
+        assert!(Config::from_json_str(json).is_err());
+    }
+
+    #[test]
+    pub fn test_grok_patterns() {
+        let json = r#"{
+            "grok_patterns": { "STATE": "%{WORD}(?:_%{WORD})*" },
+            "prefix": "%{TIMESTAMP_ISO8601:ts} %{LOGLEVEL:level} ",
+            "state_patterns": [
+                { "pattern": "Set state to %{STATE:state}", "color": "30" }
+            ]
+        }"#;
+
+        let config = Config::from_json_str(json).unwrap();
+        let prefix = config.prefix.unwrap();
+        assert!(prefix.starts_with("(?P<ts>"));
+        let captures = config.states[0]
+            .regex
+            .captures("2020-01-01 10:00:05 INFO Set state to main_menu")
+            .unwrap();
+        assert_eq!(&captures["level"], "INFO");
+        assert_eq!(&captures["state"], "main_menu");
+
+        let json = r#"{ "prefix": "%{UNKNOWN} " }"#;
+        assert!(Config::from_json_str(json).is_err());
+
+        let json = r#"{ "grok_patterns": { "STATE": 1 } }"#;
+        assert!(Config::from_json_str(json).is_err());
+    }
+
+    #[test]
+    pub fn test_yaml_and_toml_config() {
+        let config_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+        for name in &["example.json", "example.yaml", "example.toml"] {
+            let path = config_path.join(name);
+            let config = Config::from_path(&path, ConfigFormat::from_path(&path)).unwrap();
+            assert_eq!(
+                config.prefix.as_deref(),
+                Some(r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2} ")
+            );
+            let pattern_colors = config.pattern_colors.unwrap();
+            assert_eq!(pattern_colors.len(), 3);
+            assert_eq!(pattern_colors[2].color, Color::Fixed(88));
+            assert_eq!(config.events[0].color, Some(Color::Fixed(29)));
+            assert_eq!(config.states[0].color, Some(Color::Fixed(30)));
+        }
+
+        let yaml = concat!(
+            "prefix: x\n",
+            "state_patterns:\n",
+            "  - pattern: Set state to\n",
+            "    color: 300\n"
+        );
+        match Config::parse(yaml, ConfigFormat::Yaml) {
+            Err(ConfigError::Schema(path, location, _)) => {
+                assert_eq!(path, "state_patterns[0].color");
+                assert_eq!(location, Some(Location { line: 4, column: 12 }));
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        let toml = "prefix = \"x\"\n[[state_patterns]]\npattern = \"Set state to\"\ncolour = 30\n";
+        match Config::parse(toml, ConfigFormat::Toml) {
+            Err(ConfigError::Schema(path, _, _)) => assert_eq!(path, "state_patterns[0].colour"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert!(Config::parse("prefix = \"x\n", ConfigFormat::Toml).is_err());
+
+        assert_eq!(ConfigFormat::from_path(Path::new("logan.yml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("logan.config")), ConfigFormat::Json);
+    }
+
+    #[test]
+    pub fn test_extends_config() {
+        let extends_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/extends");
+        let config = Config::from_path(&extends_path.join("app.json"), ConfigFormat::Json).unwrap();
+        let prefix = r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2} ";
+        assert_eq!(config.prefix.as_deref(), Some(prefix));
+
+        // INFO is overridden in place, ERROR is added
+        let pattern_colors = config
+            .pattern_colors
+            .unwrap()
+            .into_iter()
+            .map(|pattern_color| (pattern_color.regex.as_str()[prefix.len()..].to_owned(), pattern_color.color))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pattern_colors,
+            vec![
+                ("INFO".to_owned(), Color::Fixed(30)),
+                ("WARN".to_owned(), Color::Fixed(24)),
+                ("ERROR".to_owned(), Color::Fixed(88)),
+            ]
+        );
+        assert_eq!(config.events.len(), 1);
+        assert_eq!(config.events[0].color, Some(Color::Fixed(29)));
+        let captures = config.states[0]
+            .regex
+            .captures("2020-01-01 10:00:05 INFO Set state to main_menu")
+            .unwrap();
+        assert_eq!(&captures["state"], "main_menu");
+
+        match Config::from_path(&extends_path.join("cycle_a.json"), ConfigFormat::Json) {
+            Err(ConfigError::IncludeCycle(paths)) => {
+                let names = paths
+                    .iter()
+                    .map(|path| path.file_name().unwrap().to_str().unwrap())
+                    .collect::<Vec<_>>();
+                assert_eq!(names, vec!["cycle_a.json", "cycle_b.json", "cycle_a.json"]);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        match Config::from_path(&extends_path.join("missing_base.json"), ConfigFormat::Json) {
+            Err(ConfigError::Io(path, _)) => assert!(path.ends_with("missing.json")),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    pub fn test_extends_with_another_prefix() {
+        let json = r#"{
+            "extends": "tests/extends/base.yaml",
+            "prefix": "\\[(?P<thread>\\w+)\\] "
+        }"#;
+
+        // The patterns of the base are compiled with the prefix of the config extending it
+        let config = Config::from_json_str(json).unwrap();
+        assert_eq!(config.prefix.as_deref(), Some(r"\[(?P<thread>\w+)\] "));
+        let pattern_colors = config.pattern_colors.unwrap();
+        assert_eq!(pattern_colors[0].regex.as_str(), r"\[(?P<thread>\w+)\] INFO");
+        assert!(pattern_colors[0].regex.is_match("[main] INFO Set state to main_menu"));
+        assert!(!pattern_colors[0].regex.is_match("2020-01-01 10:00:05 INFO Set state to main_menu"));
+    }
+
+    #[test]
+    pub fn test_config_errors() {
+        let json = concat!(
+            "{\n",
+            "    \"prefix\": \"INFO \",\n",
+            "    \"event_pattern\": []\n",
+            "}"
+        );
+        let err = Config::from_json_str(json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            concat!(
+                r#"Invalid configuration file: Invalid "event_pattern" at line 3 column 19. (unknown field "#,
+                "`event_pattern`, expected one of `extends`, `prefix`, `timestamp`, `multiline`, `input_format`, ",
+                "`template`, `container`, `grok_patterns`, `pattern_colors`, `event_patterns`, `state_patterns`, ",
+                "`field_counts`)"
+            )
+        );
+
+        let json = concat!(
+            "{\n",
+            "    \"event_patterns\": [\n",
+            "        { \"start_pattern\": \"down\", \"end_pattern\": \"up\" },\n",
+            "        { \"start_pattern\": \"down\", \"end_pattern\": \"up\", \"color\": \"red\" }\n",
+            "    ]\n",
+            "}"
+        );
+        match Config::from_json_str(json) {
+            Err(ConfigError::Schema(path, location, _)) => {
+                assert_eq!(path, "event_patterns[1].color");
+                assert_eq!(location, Some(Location { line: 4, column: 70 }));
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        match Config::from_json_str(r#"{ "timestamp": { "format": 1 } }"#) {
+            Err(ConfigError::Schema(path, _, _)) => assert_eq!(path, "timestamp.format"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let json = r#"{
+            "event_patterns": [
+                { "start_pattern": "\\[(?P<id>\\w+)\\] Started", "end_pattern": "Finished", "key": "id" }
+            ]
+        }"#;
+        match Config::from_json_str(json) {
+            Err(ConfigError::InvalidValue(name, _)) => assert_eq!(name, "event_patterns[0].key"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let json = r#"{ "event_patterns": [{ "start_pattern": "a", "end_pattern": "b", "timeout": "30 days" }] }"#;
+        match Config::from_json_str(json) {
+            Err(ConfigError::Schema(path, _, _)) => assert_eq!(path, "event_patterns[0].timeout"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let json = r#"{
+            "event_patterns": [{ "start_pattern": "a", "end_pattern": "b", "children": [{ "start_pattern": "(" }] }]
+        }"#;
+        match Config::from_json_str(json) {
+            Err(ConfigError::Schema(path, _, _)) => assert_eq!(path, "event_patterns[0].children[0]"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let json = r#"{
+            "event_patterns": [{
+                "start_pattern": "a", "end_pattern": "b", "children": [{ "start_pattern": "(", "end_pattern": "d" }]
+            }]
+        }"#;
+        match Config::from_json_str(json) {
+            Err(ConfigError::Regex(name, _)) => assert_eq!(name, "event_patterns[0].children[0].start_pattern"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let json = r#"{ "event_patterns": [{ "start_pattern": "a", "end_pattern": "b", "max_lines": 1 }] }"#;
+        match Config::from_json_str(json) {
+            Err(ConfigError::InvalidValue(name, _)) => assert_eq!(name, "event_patterns[0].max_lines"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let json = r#"{
+            "event_patterns": [{ "start_pattern": "a", "end_pattern": "b", "timeout": 10, "max_lines": 5 }]
+        }"#;
+        let config = Config::from_json_str(json).unwrap();
+        assert_eq!(config.events[0].timeout, Some(EventTimeout::Lines(10)));
+        assert_eq!(config.events[0].max_lines, Some(5));
+        match Config::from_json_str(r#"{ "state_patterns": [{ "pattern": "(" }] }"#) {
+            Err(ConfigError::Regex(name, _)) => assert_eq!(name, "state_patterns[0].pattern"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert!(matches!(
+            Config::from_json_str(r#"{ "prefix": "INFO " "#),
+            Err(ConfigError::JsonParse(_))
+        ));
+    }
+
     #[test]
     pub fn test_empty_config_file() {
         let json = r#"{}"#;
 
         let config = Config::from_json_str(json).unwrap();
+        assert!(config.prefix.is_none());
+        assert!(config.timestamp.is_none());
+        assert!(config.multiline.is_none());
+        assert_eq!(config.input_format, InputFormat::Text);
+        assert!(config.template.is_none());
+        assert!(config.container.is_none());
         assert!(config.pattern_colors.is_none());
         assert!(config.events.is_empty());
         assert!(config.states.is_empty());
+        assert!(config.field_counts.is_empty());
     }
 }
@@ -0,0 +1,525 @@
+use crate::{
+    formats::{ContainerDecoder, ContainerFormat, InputFormat},
+    timestamp::{TimestampFormat, TimestampParser},
+};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The share of the sample lines that must be in a format for it to be detected
+const MIN_MATCH_RATIO: f64 = 0.8;
+
+/// Structured formats in the order they are tried. Logfmt is the last since its lines are the least distinctive.
+const STRUCTURED_FORMATS: [&str; 5] = ["json", "combined", "common", "syslog", "logfmt"];
+
+const CONTAINER_FORMATS: [&str; 2] = ["docker", "cri"];
+
+const LEVEL_FIELDS: [&str; 5] = ["level", "severity", "lvl", "log.level", "loglevel"];
+const TIMESTAMP_FIELDS: [&str; 5] = ["timestamp", "time", "ts", "@timestamp", "time_local"];
+const MESSAGE_FIELDS: [&str; 3] = ["msg", "message", "log"];
+
+/// Log levels by severity with their colors. Colors of the levels in tests/example.json are kept.
+const LEVEL_COLORS: [(&str, &str); 14] = [
+    ("TRACE", "242"),
+    ("DEBUG", "244"),
+    ("INFO", "28"),
+    ("NOTICE", "30"),
+    ("WARN", "24"),
+    ("WARNING", "24"),
+    ("ERR", "88"),
+    ("ERROR", "88"),
+    ("CRIT", "124"),
+    ("CRITICAL", "124"),
+    ("ALERT", "124"),
+    ("EMERG", "124"),
+    ("FATAL", "124"),
+    ("SEVERE", "124"),
+];
+
+/// A config file guessed from a sample of a log file. It's written as JSON, one of the formats `Config::from_path` reads.
+#[derive(Debug, Default, Serialize)]
+pub struct DetectedConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DetectedTimestamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pattern_colors: Vec<DetectedPatternColor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub event_patterns: Vec<DetectedEventPatterns>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub state_patterns: Vec<DetectedStatePattern>,
+    /// The field holding the message of structured records
+    #[serde(skip)]
+    pub message_field: Option<String>,
+    /// The messages of the sampled records, i.e. the text after the prefix or the message field
+    #[serde(skip)]
+    pub messages: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DetectedTimestamp {
+    pub format: String,
+    /// Captures the timestamp if it's wrapped, e.g. in brackets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DetectedPatternColor {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub pattern: String,
+    pub color: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DetectedEventPatterns {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub start_pattern: String,
+    pub end_pattern: String,
+    pub color: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DetectedStatePattern {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub pattern: String,
+    pub color: String,
+}
+
+impl DetectedConfig {
+    /// Formats the config like tests/example.json
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let mut json = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        self.serialize(&mut serde_json::Serializer::with_formatter(&mut json, formatter))?;
+        Ok(String::from_utf8_lossy(&json).into_owned())
+    }
+}
+
+/// Guesses the format of the lines: the container log format, a structured input format, or the timestamp prefix and
+/// the log levels of text
+pub fn detect<S: AsRef<str>>(lines: &[S]) -> DetectedConfig {
+    let lines = lines
+        .iter()
+        .map(|line| line.as_ref())
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return DetectedConfig::default();
+    }
+
+    for name in &CONTAINER_FORMATS {
+        let container_format = name.parse::<ContainerFormat>().unwrap();
+        let decoded_count = lines
+            .iter()
+            .filter(|line| container_format.decode(line).is_some())
+            .count();
+        if is_most(decoded_count, lines.len()) {
+            let mut decoder = ContainerDecoder::new(container_format);
+            let mut container_lines = lines
+                .iter()
+                .filter_map(|line| decoder.push_line(line))
+                .collect::<Vec<_>>();
+            container_lines.extend(decoder.flush());
+            let texts = container_lines
+                .iter()
+                .map(|line| line.text.as_str())
+                .collect::<Vec<_>>();
+            let mut detected = detect_content(&texts);
+            detected.container = Some((*name).to_owned());
+            if detected.timestamp.is_none() {
+                detected.timestamp = Some(DetectedTimestamp {
+                    format: "rfc3339".to_owned(),
+                    pattern: None,
+                    field: Some("time".to_owned()),
+                });
+            }
+            return detected;
+        }
+    }
+
+    detect_content(&lines)
+}
+
+fn detect_content(lines: &[&str]) -> DetectedConfig {
+    for name in &STRUCTURED_FORMATS {
+        let input_format = name.parse::<InputFormat>().unwrap();
+        let records = lines
+            .iter()
+            .filter_map(|line| input_format.parse_fields(line))
+            .filter(|fields| *name != "logfmt" || is_logfmt_record(fields))
+            .collect::<Vec<_>>();
+        if is_most(records.len(), lines.len()) {
+            return detect_structured(name, &records);
+        }
+    }
+
+    detect_text(lines)
+}
+
+fn is_most(count: usize, total: usize) -> bool {
+    count as f64 >= total as f64 * MIN_MATCH_RATIO
+}
+
+/// Most text lines have a `=` somewhere, logfmt records consist of `key=value` pairs only
+fn is_logfmt_record(fields: &HashMap<String, String>) -> bool {
+    fields.len() >= 2 && fields.values().all(|value| !value.is_empty())
+}
+
+fn detect_structured(name: &str, records: &[HashMap<String, String>]) -> DetectedConfig {
+    let find_field = |candidates: &[&str]| {
+        candidates
+            .iter()
+            .find(|field| records.iter().any(|fields| fields.contains_key(**field)))
+            .map(|field| (*field).to_owned())
+    };
+    let level_field = find_field(&LEVEL_FIELDS);
+    let timestamp_field = find_field(&TIMESTAMP_FIELDS);
+    let message_field = find_field(&MESSAGE_FIELDS);
+
+    let timestamp = timestamp_field.as_ref().and_then(|field| {
+        let format = match name {
+            "combined" | "common" => "%d/%b/%Y:%H:%M:%S %z".to_owned(),
+            _ => detect_timestamp_format(records.iter().filter_map(|fields| fields.get(field)))?,
+        };
+        Some(DetectedTimestamp {
+            format,
+            pattern: None,
+            field: Some(field.clone()),
+        })
+    });
+
+    let pattern_colors = match name {
+        "combined" | "common" => vec![
+            DetectedPatternColor {
+                field: Some("status".to_owned()),
+                pattern: "^5".to_owned(),
+                color: "88".to_owned(),
+            },
+            DetectedPatternColor {
+                field: Some("status".to_owned()),
+                pattern: "^4".to_owned(),
+                color: "24".to_owned(),
+            },
+        ],
+        _ => level_field
+            .as_ref()
+            .map(|field| {
+                let values = records
+                    .iter()
+                    .filter_map(|fields| fields.get(field).map(|value| value.as_str()));
+                level_colors(values)
+                    .into_iter()
+                    .map(|(value, color)| DetectedPatternColor {
+                        field: Some(field.clone()),
+                        pattern: format!("^{}$", regex::escape(&value)),
+                        color: color.to_owned(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    // Structured lines are hard to read, so they're displayed by a template if there's a message
+    let template = match name {
+        "json" | "logfmt" => message_field.as_ref().map(|message_field| {
+            vec![timestamp_field.as_ref(), level_field.as_ref(), Some(message_field)]
+                .into_iter()
+                .flatten()
+                .map(|field| format!("{{{}}}", field))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }),
+        _ => None,
+    };
+
+    let messages = match &message_field {
+        Some(field) => records.iter().filter_map(|fields| fields.get(field).cloned()).collect(),
+        None => Vec::new(),
+    };
+
+    DetectedConfig {
+        input_format: Some(name.to_owned()),
+        timestamp,
+        template,
+        pattern_colors,
+        message_field,
+        messages,
+        ..Default::default()
+    }
+}
+
+fn detect_text(lines: &[&str]) -> DetectedConfig {
+    let iso8601_regex =
+        Regex::new(r"^(\[?)(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?)(\]?)\s")
+            .unwrap();
+    let candidates = [
+        (
+            Regex::new(r"^\[?[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}\]?\s").unwrap(),
+            r"[A-Z][a-z]{2} [ \d][\d] [\d]{2}:[\d]{2}:[\d]{2}",
+            "syslog",
+        ),
+        (Regex::new(r"^\[?\d{13}\]?\s").unwrap(), r"[\d]{13}", "epoch_millis"),
+        (Regex::new(r"^\[?\d{10}\]?\s").unwrap(), r"[\d]{10}", "epoch_seconds"),
+    ];
+
+    // Lines without a timestamp may be continuation lines, e.g. stack traces, but the first line should start a record
+    let first_line = lines[0];
+    let prefix = match iso8601_regex.captures(first_line) {
+        // The prefix is made from the first timestamp, so it follows its separators, fractions and time zone
+        Some(captures) => Some((
+            bracket_pattern(&generalize_digits(&captures[2]), !captures[1].is_empty()),
+            "iso8601",
+        )),
+        None => candidates
+            .iter()
+            .find(|(regex, _, _)| regex.is_match(first_line))
+            .map(|(_, pattern, format)| (bracket_pattern(pattern, first_line.starts_with('[')), *format)),
+    };
+
+    let (prefix, format) = match prefix {
+        Some((prefix, format)) => (format!("{} ", prefix), format),
+        None => return DetectedConfig::default(),
+    };
+    // The timestamp parsers expect the timestamp at the start of the line, so a wrapped one has to be captured
+    let timestamp_pattern = if prefix.starts_with(r"\[") {
+        Some(r"^\[([^\]]+)\]".to_owned())
+    } else {
+        None
+    };
+    let prefix_regex = Regex::new(&format!("^(?:{})", prefix)).unwrap();
+    let level_regex = Regex::new(r"^\[?([A-Za-z]+)\]?:?\s").unwrap();
+    let messages = lines
+        .iter()
+        .filter_map(|line| Some(&line[prefix_regex.find(line)?.end()..]))
+        .collect::<Vec<_>>();
+    let levels = messages
+        .iter()
+        .filter_map(|message| Some(level_regex.find(message)?.as_str().trim_end()));
+
+    DetectedConfig {
+        timestamp: Some(DetectedTimestamp {
+            format: format.to_owned(),
+            pattern: timestamp_pattern,
+            field: None,
+        }),
+        pattern_colors: level_colors(levels)
+            .into_iter()
+            .map(|(level, color)| DetectedPatternColor {
+                field: None,
+                pattern: regex::escape(&level),
+                color: color.to_owned(),
+            })
+            .collect(),
+        prefix: Some(prefix),
+        messages: messages.into_iter().map(|message| message.to_owned()).collect(),
+        ..Default::default()
+    }
+}
+
+/// Replaces the digits of a timestamp with digit classes, e.g. `2020-01-01` gives `[\d]{4}-[\d]{2}-[\d]{2}`
+fn generalize_digits(timestamp: &str) -> String {
+    let mut pattern = String::new();
+    let mut chars = timestamp.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut count = 1;
+            while chars.next_if(|c| c.is_ascii_digit()).is_some() {
+                count += 1;
+            }
+            pattern.push_str(&format!(r"[\d]{{{}}}", count));
+        } else if c == '-' {
+            pattern.push(c);
+        } else {
+            pattern.push_str(&regex::escape(&c.to_string()));
+        }
+    }
+    pattern
+}
+
+fn bracket_pattern(pattern: &str, bracketed: bool) -> String {
+    if bracketed {
+        format!(r"\[{}\]", pattern)
+    } else {
+        pattern.to_owned()
+    }
+}
+
+fn detect_timestamp_format<'a, I: Iterator<Item = &'a String>>(mut values: I) -> Option<String> {
+    let value = values.next()?;
+    let formats = [
+        TimestampFormat::Iso8601,
+        TimestampFormat::Syslog,
+        TimestampFormat::EpochMillis,
+        TimestampFormat::EpochSeconds,
+    ];
+    formats
+        .iter()
+        .filter(|format| match format {
+            TimestampFormat::EpochMillis => value.len() >= 13,
+            TimestampFormat::EpochSeconds => value.len() >= 10,
+            _ => true,
+        })
+        .find(|format| TimestampParser::new((*format).clone(), None).parse(value).is_some())
+        .map(|format| {
+            match format {
+                TimestampFormat::Iso8601 => "iso8601",
+                TimestampFormat::Syslog => "syslog",
+                TimestampFormat::EpochMillis => "epoch_millis",
+                _ => "epoch_seconds",
+            }
+            .to_owned()
+        })
+}
+
+/// Returns the known log levels among the values, ordered by severity, with their colors
+fn level_colors<'a, I: Iterator<Item = &'a str>>(values: I) -> Vec<(String, &'static str)> {
+    let values = values.collect::<HashSet<_>>();
+    let mut levels = values
+        .into_iter()
+        .filter_map(|value| {
+            let name = value.trim_matches(|c| c == '[' || c == ']' || c == ':').to_uppercase();
+            let severity = LEVEL_COLORS.iter().position(|(level, _)| *level == name)?;
+            Some((severity, value.to_owned()))
+        })
+        .collect::<Vec<_>>();
+    levels.sort();
+    levels
+        .into_iter()
+        .map(|(severity, value)| (value, LEVEL_COLORS[severity].1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, DetectedPatternColor, DetectedTimestamp};
+    use crate::{config::Config, record::RecordParser, timestamp::parse_time_bound};
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader},
+        path::PathBuf,
+    };
+
+    #[test]
+    fn test_detect_text_format() {
+        let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
+        let lines = BufReader::new(File::open(test_log_path).unwrap())
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let detected = detect(&lines);
+        assert_eq!(
+            detected.prefix.as_deref(),
+            Some(r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2} ")
+        );
+        assert_eq!(
+            detected.timestamp,
+            Some(DetectedTimestamp {
+                format: "iso8601".to_owned(),
+                pattern: None,
+                field: None
+            })
+        );
+        let patterns = detected
+            .pattern_colors
+            .iter()
+            .map(|pattern_color| (pattern_color.pattern.as_str(), pattern_color.color.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(patterns, vec![("INFO", "28"), ("WARN", "24"), ("ERROR", "88")]);
+
+        let config = Config::from_json_str(detected.to_json().unwrap()).unwrap();
+        assert_eq!(config.pattern_colors.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_detect_bracketed_timestamps() {
+        let lines = [
+            "[2020-01-01 10:00:00] INFO Start of log file",
+            "[2020-01-01 10:00:01] WARN Low memory",
+        ];
+        let detected = detect(&lines);
+        assert_eq!(
+            detected.prefix.as_deref(),
+            Some(r"\[[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2}\] ")
+        );
+
+        let config = Config::from_json_str(detected.to_json().unwrap()).unwrap();
+        let record_parser = RecordParser::new(config.prefix.as_deref(), config.timestamp).unwrap();
+        assert_eq!(
+            lines.iter().map(|line| record_parser.parse_timestamp(line)).collect::<Vec<_>>(),
+            vec![parse_time_bound("2020-01-01 10:00:00"), parse_time_bound("2020-01-01 10:00:01")]
+        );
+    }
+
+    #[test]
+    fn test_detect_structured_formats() {
+        let detected = detect(&[
+            r#"{"ts":"2020-01-01T10:00:00Z","level":"info","msg":"Start of log file"}"#,
+            r#"{"ts":"2020-01-01T10:00:50Z","level":"error","msg":"Failed to start application"}"#,
+        ]);
+        assert_eq!(detected.input_format.as_deref(), Some("json"));
+        assert_eq!(detected.template.as_deref(), Some("{ts} {level} {msg}"));
+        assert_eq!(
+            detected.timestamp,
+            Some(DetectedTimestamp {
+                format: "iso8601".to_owned(),
+                pattern: None,
+                field: Some("ts".to_owned())
+            })
+        );
+        assert_eq!(
+            detected.pattern_colors[1],
+            DetectedPatternColor {
+                field: Some("level".to_owned()),
+                pattern: "^error$".to_owned(),
+                color: "88".to_owned()
+            }
+        );
+        assert!(Config::from_json_str(detected.to_json().unwrap()).is_ok());
+
+        let detected = detect(&["<34>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick on /dev/pts/8"]);
+        assert_eq!(detected.input_format.as_deref(), Some("syslog"));
+        assert_eq!(detected.pattern_colors[0].pattern, "^crit$");
+
+        let detected =
+            detect(&[r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 2326 "-" "curl""#]);
+        assert_eq!(detected.input_format.as_deref(), Some("combined"));
+        assert!(Config::from_json_str(detected.to_json().unwrap()).is_ok());
+
+        let detected = detect(&["time=2020-01-01T10:00:00Z level=warn msg=\"Disk almost full\""]);
+        assert_eq!(detected.input_format.as_deref(), Some("logfmt"));
+        assert_eq!(detected.template.as_deref(), Some("{time} {level} {msg}"));
+
+        let detected = detect(&[
+            "2020-01-01T10:00:50.000000001Z stderr P 2020-01-01 10:00:50 ",
+            "2020-01-01T10:00:50.000000002Z stderr F ERROR Failed to start application",
+        ]);
+        assert_eq!(detected.container.as_deref(), Some("cri"));
+        assert_eq!(
+            detected.prefix.as_deref(),
+            Some(r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2} ")
+        );
+        assert!(Config::from_json_str(detected.to_json().unwrap()).is_ok());
+
+        let detected = detect(&[
+            r#"{"log":"{\"level\":\"info\",\"msg\":\"Started\"}\n","stream":"stdout","time":"2020-01-01T10:00:50Z"}"#,
+            r#"{"log":"{\"level\":\"error\",\"msg\":\"Failed\"}\n","stream":"stderr","time":"2020-01-01T10:00:51Z"}"#,
+        ]);
+        assert_eq!(detected.container.as_deref(), Some("docker"));
+        assert_eq!(detected.input_format.as_deref(), Some("json"));
+        assert_eq!(detected.timestamp.unwrap().field.as_deref(), Some("time"));
+    }
+}
@@ -1,31 +1,56 @@
+use itertools::Itertools;
 use std::{
     error::Error,
     fmt::{Debug, Display, Formatter},
+    io,
     num::ParseIntError,
+    path::PathBuf,
 };
 
 pub enum ConfigError {
+    Io(PathBuf, io::Error),
     JsonParse(serde_json::error::Error),
-    JsonType(&'static str, JsonType),
-    Regex(&'static str, regex::Error),
-    ParseInt(&'static str, ParseIntError),
+    YamlParse(serde_yaml::Error),
+    TomlParse(toml::de::Error),
+    /// A value doesn't match the schema of config files, e.g. it has a wrong type or its key is unknown
+    Schema(String, Option<Location>, String),
+    Regex(String, regex::Error),
+    InvalidValue(String, String),
+    Grok(String, GrokError),
+    /// An error in a file extended by the config file
+    Include(PathBuf, Box<ConfigError>),
+    /// Config files that extend each other, from the first one to the repeated one
+    IncludeCycle(Vec<PathBuf>),
+}
+
+impl ConfigError {
+    fn message(&self) -> String {
+        match self {
+            ConfigError::Io(path, err) => format!("Failed to read {}. ({})", path.display(), err),
+            ConfigError::JsonParse(err) => format!("{}", err),
+            ConfigError::YamlParse(err) => format!("{}", err),
+            ConfigError::TomlParse(err) => format!("{}", err),
+            ConfigError::Schema(path, location, message) => {
+                // The location is moved from the end of the message of the parser next to the path
+                let location = location.map(|location| format!(" at {}", location)).unwrap_or_default();
+                let message = message.strip_suffix(&location).unwrap_or(message);
+                format!(r#"Invalid "{}"{}. ({})"#, path, location, message)
+            }
+            ConfigError::Regex(name, err) => format!(r#"Invalid regex for "{}". ({})"#, name, err),
+            ConfigError::InvalidValue(name, err) => format!(r#"Invalid value for "{}". ({})"#, name, err),
+            ConfigError::Grok(name, err) => format!(r#"Invalid grok pattern for "{}". ({})"#, name, err),
+            ConfigError::Include(path, err) => format!("{} (in {})", err.message(), path.display()),
+            ConfigError::IncludeCycle(paths) => format!(
+                "Config files extend each other: {}",
+                paths.iter().map(|path| path.display().to_string()).join(" -> ")
+            ),
+        }
+    }
 }
 
 impl Display for ConfigError {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "Invalid configuration file: {}",
-            match self {
-                ConfigError::JsonParse(err) => format!("{}", err),
-                ConfigError::JsonType(name, expected_type) => format!(
-                    r#"Invalid Json value type for "{}" (Expected: {})"#,
-                    name, expected_type
-                ),
-                ConfigError::Regex(name, err) => format!(r#"Invalid regex for "{}". ({})"#, name, err),
-                ConfigError::ParseInt(name, err) => format!(r#"Failed to parse "{}". ({})"#, name, err),
-            }
-        )
+        write!(f, "Invalid configuration file: {}", self.message())
     }
 }
 
@@ -35,11 +60,17 @@ impl Debug for ConfigError {
             f,
             "ConfigError({})",
             match self {
+                ConfigError::Io(path, err) => format!("path: {:?}, err: {:?}", path, err),
                 ConfigError::JsonParse(err) => format!("{:?}", err),
-                ConfigError::JsonType(name, expected_type) =>
-                    format!("name: {:?}, expected_type: {:?}", name, expected_type),
+                ConfigError::YamlParse(err) => format!("{:?}", err),
+                ConfigError::TomlParse(err) => format!("{:?}", err),
+                ConfigError::Schema(path, location, message) =>
+                    format!("path: {:?}, location: {:?}, message: {:?}", path, location, message),
                 ConfigError::Regex(name, err) => format!("name: {:?}, err: {:?}", name, err),
-                ConfigError::ParseInt(name, err) => format!("name: {:?}, err: {:?}", name, err),
+                ConfigError::InvalidValue(name, err) => format!("name: {:?}, err: {:?}", name, err),
+                ConfigError::Grok(name, err) => format!("name: {:?}, err: {:?}", name, err),
+                ConfigError::Include(path, err) => format!("path: {:?}, err: {:?}", path, err),
+                ConfigError::IncludeCycle(paths) => format!("paths: {:?}", paths),
             }
         )
     }
@@ -53,21 +84,46 @@ impl From<serde_json::error::Error> for ConfigError {
     }
 }
 
-#[derive(Debug)]
-pub enum JsonType {
-    //Number,
-    String,
-    Array,
+/// The line and column of a value in a config file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
 }
 
-impl Display for JsonType {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        match self {
-            //JsonType::Number => write!(f, "Number"),
-            JsonType::String => write!(f, "String"),
-            JsonType::Array => write!(f, "Array"),
+impl Location {
+    /// Returns `None` if the error has no location, e.g. an I/O error
+    pub fn of_json_error(err: &serde_json::error::Error) -> Option<Self> {
+        if err.line() == 0 {
+            None
+        } else {
+            Some(Self {
+                line: err.line(),
+                column: err.column(),
+            })
         }
     }
+
+    pub fn of_yaml_error(err: &serde_yaml::Error) -> Option<Self> {
+        err.location().map(|location| Self {
+            line: location.line(),
+            column: location.column(),
+        })
+    }
+
+    /// The location of TOML errors starts from zero
+    pub fn of_toml_error(err: &toml::de::Error) -> Option<Self> {
+        err.line_col().map(|(line, column)| Self {
+            line: line + 1,
+            column: column + 1,
+        })
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "line {} column {}", self.line, self.column)
+    }
 }
 
 #[derive(Debug)]
@@ -88,3 +144,52 @@ impl Display for ParseColorError {
         fmt.write_str(self.message.as_str())
     }
 }
+
+#[derive(Debug)]
+pub enum InputError {
+    Pattern(String, glob::PatternError),
+    Glob(glob::GlobError),
+    NoMatch(String),
+}
+
+impl Error for InputError {}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            InputError::Pattern(pattern, err) => write!(f, r#"Invalid input pattern "{}" ({})"#, pattern, err),
+            InputError::Glob(err) => write!(f, "Failed to read input path: {}", err),
+            InputError::NoMatch(pattern) => write!(f, r#"No input files match "{}""#, pattern),
+        }
+    }
+}
+
+impl From<glob::GlobError> for InputError {
+    fn from(err: glob::GlobError) -> Self {
+        InputError::Glob(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum GrokError {
+    UnknownPattern(String),
+    Recursive(String),
+    Syntax(String),
+    /// A capture name and the pattern that uses it again
+    DuplicateCapture(String, String),
+}
+
+impl Error for GrokError {}
+
+impl Display for GrokError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            GrokError::UnknownPattern(name) => write!(f, r#"Unknown grok pattern "{}""#, name),
+            GrokError::Recursive(name) => write!(f, r#"Grok pattern "{}" is nested too deep or recursive"#, name),
+            GrokError::Syntax(pattern) => write!(f, r#"Unclosed "%{{" in "{}""#, pattern),
+            GrokError::DuplicateCapture(name, pattern) => {
+                write!(f, r#"Grok pattern "{}" captures "{}", which is already captured"#, pattern, name)
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::pipeline::Pipeline;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A file that can be reopened when it gets rotated or truncated, similar to `tail -F`
+pub struct FollowFile {
+    path: PathBuf,
+    file: File,
+    position: u64,
+}
+
+impl FollowFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = File::open(&path)?;
+        Ok(Self { path, file, position: 0 })
+    }
+
+    /// Checks whether the followed file has been replaced or truncated and starts reading it from the beginning if so.
+    /// Returns `true` if the file was reopened or rewound.
+    pub fn check_rotation(&mut self) -> io::Result<bool> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // The file may be missing for a moment while it's being rotated
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        if is_replaced(&self.file, &metadata)? {
+            self.file = File::open(&self.path)?;
+            self.position = 0;
+            Ok(true)
+        } else if metadata.len() < self.position {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.position = 0;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl Read for FollowFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(unix)]
+fn is_replaced(file: &File, metadata: &fs::Metadata) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let current = file.metadata()?;
+    Ok(current.dev() != metadata.dev() || current.ino() != metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn is_replaced(_file: &File, _metadata: &fs::Metadata) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Flags set by signal handlers while following a file
+struct SignalFlags {
+    terminate: Arc<AtomicBool>,
+    print_results: Arc<AtomicBool>,
+}
+
+impl SignalFlags {
+    fn register() -> io::Result<Self> {
+        use signal_hook::{consts::signal, flag};
+
+        let terminate = Arc::new(AtomicBool::new(false));
+        let print_results = Arc::new(AtomicBool::new(false));
+        // SIGINT prints the results as well since we exit afterwards
+        flag::register(signal::SIGINT, Arc::clone(&terminate))?;
+        flag::register(signal::SIGTERM, Arc::clone(&terminate))?;
+        #[cfg(unix)]
+        flag::register(signal::SIGUSR1, Arc::clone(&print_results))?;
+
+        Ok(Self {
+            terminate,
+            print_results,
+        })
+    }
+}
+
+/// Processes the lines of the file at `path` and keeps waiting for new lines until SIGINT or SIGTERM is received.
+/// Results are printed on SIGUSR1.
+pub fn follow(path: &Path, pipeline: &mut Pipeline) -> io::Result<()> {
+    let signal_flags = SignalFlags::register()?;
+    let mut reader = BufReader::new(FollowFile::open(path)?);
+    let mut line = String::new();
+
+    while !signal_flags.terminate.load(Ordering::Relaxed) {
+        if signal_flags.print_results.swap(false, Ordering::Relaxed) {
+            pipeline.print_results()?;
+        }
+
+        if reader.read_line(&mut line)? > 0 && line.ends_with('\n') {
+            pipeline.process_line(trim_line_ending(&line))?;
+            line.clear();
+            continue;
+        }
+
+        // Reached the end of the file. A partially written line is kept until the rest of it arrives
+        if reader.get_mut().check_rotation()? {
+            if !line.is_empty() {
+                pipeline.process_line(trim_line_ending(&line))?;
+                line.clear();
+            }
+            continue;
+        }
+        // Nothing more to read for now, so the last record is considered complete
+        pipeline.flush()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    if !line.is_empty() {
+        pipeline.process_line(trim_line_ending(&line))?;
+    }
+
+    Ok(())
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FollowFile;
+    use std::{
+        env,
+        fs::{self, OpenOptions},
+        io::{Read, Write},
+        process,
+    };
+
+    #[test]
+    fn test_follow_file_truncated() {
+        let path = env::temp_dir().join(format!("logan_follow_test_{}.log", process::id()));
+        fs::write(&path, "first line\nsecond line\n").unwrap();
+
+        let mut follow_file = FollowFile::open(&path).unwrap();
+        let mut content = String::new();
+        follow_file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "first line\nsecond line\n");
+        assert!(!follow_file.check_rotation().unwrap());
+
+        OpenOptions::new().write(true).truncate(true).open(&path).unwrap().write_all(b"new\n").unwrap();
+        assert!(follow_file.check_rotation().unwrap());
+
+        content.clear();
+        follow_file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "new\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// The Common Log Format of Apache and other web servers, written as an nginx `log_format`
+pub const COMMON_LOG_FORMAT: &str = r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent"#;
+
+/// The Combined Log Format, which is also the default format of nginx
+pub const COMBINED_LOG_FORMAT: &str = concat!(
+    r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent "#,
+    r#""$http_referer" "$http_user_agent""#
+);
+
+/// Fields that are added under a shorter name as well
+const FIELD_ALIASES: [(&str, &str); 6] = [
+    ("body_bytes_sent", "bytes"),
+    ("bytes_sent", "bytes"),
+    ("request_time", "response_time"),
+    ("http_user_agent", "user_agent"),
+    ("http_referer", "referer"),
+    ("remote_addr", "client"),
+];
+
+/// Parses access log lines by an nginx `log_format` string, e.g. `$remote_addr [$time_local] "$request" $status`.
+/// Every variable becomes a field with the same name. The `request` field is split into the `method`, `path` and
+/// `protocol` fields and some fields are available under a shorter name, e.g. `bytes`, `response_time` and
+/// `user_agent`.
+#[derive(Debug, Clone)]
+pub struct AccessLogFormat {
+    log_format: String,
+    regex: Regex,
+}
+
+impl AccessLogFormat {
+    pub fn new(log_format: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            log_format: log_format.to_owned(),
+            regex: Regex::new(&compile_log_format(log_format))?,
+        })
+    }
+
+    pub fn parse_fields(&self, line: &str) -> Option<HashMap<String, String>> {
+        let captures = self.regex.captures(line)?;
+        let mut fields = self
+            .regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_owned(), captures.name(name)?.as_str().to_owned())))
+            .collect::<HashMap<_, _>>();
+
+        for (name, alias) in &FIELD_ALIASES {
+            if let Some(value) = fields.get(*name) {
+                if !fields.contains_key(*alias) {
+                    fields.insert((*alias).to_owned(), value.clone());
+                }
+            }
+        }
+
+        if let Some(request) = fields.get("request").cloned() {
+            let mut parts = request.splitn(3, ' ');
+            for name in &["method", "path", "protocol"] {
+                if let Some(part) = parts.next() {
+                    fields.entry((*name).to_owned()).or_insert_with(|| part.to_owned());
+                }
+            }
+        }
+
+        Some(fields)
+    }
+}
+
+impl PartialEq for AccessLogFormat {
+    fn eq(&self, other: &Self) -> bool {
+        self.log_format == other.log_format
+    }
+}
+
+/// Compiles a `log_format` string into a regex. A variable matches everything up to the character following it in
+/// the format, the last variable matches the rest of the line. Only the first occurrence of a variable is captured.
+fn compile_log_format(log_format: &str) -> String {
+    let mut pattern = String::from("^");
+    let mut captured = HashSet::new();
+    let mut rest = log_format;
+
+    while let Some(start) = rest.find('$') {
+        pattern.push_str(&regex::escape(&rest[..start]));
+        rest = &rest[start + 1..];
+
+        let (name, remainder) = match rest
+            .strip_prefix('{')
+            .and_then(|braced| Some(braced.split_at(braced.find('}')?)))
+        {
+            Some((name, remainder)) => (name, &remainder[1..]),
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+        rest = remainder;
+
+        if name.is_empty() {
+            pattern.push_str(&regex::escape("$"));
+            continue;
+        }
+
+        let value_pattern = match rest.chars().next() {
+            Some(terminator) => format!("[^{}]*", regex::escape(&terminator.to_string())),
+            None => ".*".to_owned(),
+        };
+        if captured.insert(name) {
+            pattern.push_str(&format!("(?P<{}>{})", name, value_pattern));
+        } else {
+            pattern.push_str(&format!("(?:{})", value_pattern));
+        }
+    }
+
+    pattern.push_str(&regex::escape(rest));
+    pattern.push('$');
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessLogFormat, COMBINED_LOG_FORMAT, COMMON_LOG_FORMAT};
+
+    #[test]
+    fn test_parse_access_log_fields() {
+        let format = AccessLogFormat::new(COMMON_LOG_FORMAT).unwrap();
+        let fields = format
+            .parse_fields(r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#)
+            .unwrap();
+        assert_eq!(fields["remote_addr"], "127.0.0.1");
+        assert_eq!(fields["remote_user"], "frank");
+        assert_eq!(fields["time_local"], "10/Oct/2000:13:55:36 -0700");
+        assert_eq!(fields["method"], "GET");
+        assert_eq!(fields["path"], "/apache_pb.gif");
+        assert_eq!(fields["protocol"], "HTTP/1.0");
+        assert_eq!(fields["status"], "200");
+        assert_eq!(fields["bytes"], "2326");
+
+        let format = AccessLogFormat::new(COMBINED_LOG_FORMAT).unwrap();
+        let fields = format
+            .parse_fields(concat!(
+                r#"10.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "POST /login HTTP/1.1" 502 0 "-" "#,
+                r#""Mozilla/5.0 (X11; Linux x86_64)""#
+            ))
+            .unwrap();
+        assert_eq!(fields["status"], "502");
+        assert_eq!(fields["referer"], "-");
+        assert_eq!(fields["user_agent"], "Mozilla/5.0 (X11; Linux x86_64)");
+        assert_eq!(format.parse_fields("2020-01-01 10:00:00 INFO Start of log file"), None);
+
+        let format = AccessLogFormat::new(r#"$remote_addr [$time_local] "$request" $status ${request_time}s"#).unwrap();
+        let fields = format
+            .parse_fields(r#"10.0.0.1 [10/Oct/2000:13:55:36 -0700] "GET /?q=a+b HTTP/2.0" 404 0.012s"#)
+            .unwrap();
+        assert_eq!(fields["path"], "/?q=a+b");
+        assert_eq!(fields["status"], "404");
+        assert_eq!(fields["response_time"], "0.012");
+    }
+}
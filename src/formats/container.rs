@@ -0,0 +1,229 @@
+use serde_json::Value;
+use std::{collections::HashMap, str::FromStr};
+
+/// The log file format of container runtimes. The lines of the containers are unwrapped from it before they are
+/// processed, so they can be in any input format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContainerFormat {
+    /// The Docker JSON-file logging driver, e.g. `{"log":"Started\n","stream":"stdout","time":"2020-01-01T10:00:00Z"}`
+    Docker,
+    /// The CRI format of containerd and CRI-O, e.g. `2020-01-01T10:00:00.000000000Z stdout F Started`
+    Cri,
+}
+
+impl FromStr for ContainerFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "docker" => Ok(ContainerFormat::Docker),
+            "cri" => Ok(ContainerFormat::Cri),
+            format => Err(format!("Unknown container format: {}", format)),
+        }
+    }
+}
+
+/// A line written by a container with the `stream` and `time` fields of the container runtime
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContainerLine {
+    pub text: String,
+    pub fields: HashMap<String, String>,
+    partial: bool,
+}
+
+impl ContainerFormat {
+    /// Unwraps a line of a container log file. Returns `None` if the line is not in the expected format.
+    pub fn decode(&self, line: &str) -> Option<ContainerLine> {
+        match self {
+            ContainerFormat::Docker => decode_docker(line),
+            ContainerFormat::Cri => decode_cri(line),
+        }
+    }
+}
+
+fn decode_docker(line: &str) -> Option<ContainerLine> {
+    let mut object = match serde_json::from_str(line) {
+        Ok(Value::Object(object)) => object,
+        _ => return None,
+    };
+    let mut text = match object.remove("log") {
+        Some(Value::String(text)) => text,
+        _ => return None,
+    };
+
+    // Docker splits long lines into multiple entries, only the last one ends with a line break
+    let partial = !text.ends_with('\n');
+    if !partial {
+        text.pop();
+        if text.ends_with('\r') {
+            text.pop();
+        }
+    }
+
+    let fields = ["stream", "time"]
+        .iter()
+        .filter_map(|name| match object.remove(*name) {
+            Some(Value::String(value)) => Some(((*name).to_owned(), value)),
+            _ => None,
+        })
+        .collect();
+    Some(ContainerLine { text, fields, partial })
+}
+
+fn decode_cri(line: &str) -> Option<ContainerLine> {
+    let mut parts = line.splitn(4, ' ');
+    let time = parts.next()?;
+    let stream = parts.next()?;
+    // The tag may have more flags separated by colons in the future, the first one tells whether the line is partial
+    let partial = match parts.next()?.split(':').next() {
+        Some("P") => true,
+        Some("F") => false,
+        _ => return None,
+    };
+    let text = parts.next().unwrap_or_default();
+
+    let mut fields = HashMap::new();
+    fields.insert("time".to_owned(), time.to_owned());
+    fields.insert("stream".to_owned(), stream.to_owned());
+    Some(ContainerLine {
+        text: text.to_owned(),
+        fields,
+        partial,
+    })
+}
+
+/// Unwraps the lines of a container log file and joins the partial lines. The fields of a joined line are the fields
+/// of its first part. The parts of lines written to stdout and stderr can interleave, so the partial lines are joined
+/// per stream. Lines that are not in the container format are kept as they are.
+#[derive(Debug, Clone)]
+pub struct ContainerDecoder {
+    format: ContainerFormat,
+    /// The lines that are being joined by their streams, in the order they started
+    partial_lines: Vec<(String, ContainerLine)>,
+}
+
+impl ContainerDecoder {
+    pub fn new(format: ContainerFormat) -> Self {
+        Self {
+            format,
+            partial_lines: Vec::new(),
+        }
+    }
+
+    /// Decodes a line. Returns the line once it's complete.
+    pub fn push_line(&mut self, line: &str) -> Option<ContainerLine> {
+        let container_line = self.format.decode(line).unwrap_or_else(|| ContainerLine {
+            text: line.to_owned(),
+            ..Default::default()
+        });
+        let stream = container_line.fields.get("stream").cloned().unwrap_or_default();
+        match self.partial_lines.iter().position(|(partial_stream, _)| *partial_stream == stream) {
+            Some(index) => {
+                let partial_line = &mut self.partial_lines[index].1;
+                partial_line.text.push_str(&container_line.text);
+                partial_line.partial = container_line.partial;
+                if partial_line.partial {
+                    None
+                } else {
+                    Some(self.partial_lines.remove(index).1)
+                }
+            }
+            None if container_line.partial => {
+                self.partial_lines.push((stream, container_line));
+                None
+            }
+            None => Some(container_line),
+        }
+    }
+
+    /// Returns the lines that are being joined, even though their last parts are missing
+    pub fn flush(&mut self) -> Vec<ContainerLine> {
+        self.partial_lines.drain(..).map(|(_, partial_line)| partial_line).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContainerDecoder, ContainerFormat};
+
+    #[test]
+    fn test_decode_docker_lines() {
+        let mut decoder = ContainerDecoder::new(ContainerFormat::Docker);
+        let line = decoder
+            .push_line(concat!(
+                r#"{"log":"2020-01-01 10:00:00 INFO Start of log file\n","#,
+                r#""stream":"stdout","time":"2020-01-01T10:00:00.1Z"}"#
+            ))
+            .unwrap();
+        assert_eq!(line.text, "2020-01-01 10:00:00 INFO Start of log file");
+        assert_eq!(line.fields["stream"], "stdout");
+        assert_eq!(line.fields["time"], "2020-01-01T10:00:00.1Z");
+
+        assert_eq!(
+            decoder.push_line(
+                r#"{"log":"2020-01-01 10:00:50 ERROR Failed ","stream":"stderr","time":"2020-01-01T10:00:50Z"}"#
+            ),
+            None
+        );
+        let line = decoder
+            .push_line(r#"{"log":"to start application\r\n","stream":"stderr","time":"2020-01-01T10:00:51Z"}"#)
+            .unwrap();
+        assert_eq!(line.text, "2020-01-01 10:00:50 ERROR Failed to start application");
+        assert_eq!(line.fields["stream"], "stderr");
+        assert_eq!(line.fields["time"], "2020-01-01T10:00:50Z");
+
+        let line = decoder.push_line("An unknown error occurred").unwrap();
+        assert_eq!(line.text, "An unknown error occurred");
+        assert!(line.fields.is_empty());
+        assert!(decoder.flush().is_empty());
+    }
+
+    #[test]
+    fn test_decode_cri_lines() {
+        let mut decoder = ContainerDecoder::new(ContainerFormat::Cri);
+        assert_eq!(
+            decoder.push_line("2020-01-01T10:00:50.000000001Z stderr P 2020-01-01 10:00:50 "),
+            None
+        );
+        assert_eq!(
+            decoder.push_line("2020-01-01T10:00:50.000000002Z stderr P ERROR Failed"),
+            None
+        );
+        let line = decoder
+            .push_line("2020-01-01T10:00:50.000000003Z stderr F  to start application")
+            .unwrap();
+        assert_eq!(line.text, "2020-01-01 10:00:50 ERROR Failed to start application");
+        assert_eq!(line.fields["stream"], "stderr");
+        assert_eq!(line.fields["time"], "2020-01-01T10:00:50.000000001Z");
+
+        let line = decoder.push_line("2020-01-01T10:01:00Z stdout F").unwrap();
+        assert_eq!(line.text, "");
+
+        assert_eq!(decoder.push_line("2020-01-01T10:01:00Z stdout P End of"), None);
+        assert_eq!(decoder.flush()[0].text, "End of");
+        assert_eq!(
+            ContainerFormat::Cri.decode("2020-01-01 10:00:00 INFO Start of log file"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_join_interleaved_streams() {
+        let mut decoder = ContainerDecoder::new(ContainerFormat::Cri);
+        assert_eq!(decoder.push_line("2020-01-01T10:00:00Z stdout P Request "), None);
+        assert_eq!(decoder.push_line("2020-01-01T10:00:01Z stderr P ERROR Failed "), None);
+        let line = decoder.push_line("2020-01-01T10:00:02Z stdout F handled").unwrap();
+        assert_eq!(line.text, "Request handled");
+        assert_eq!(line.fields["stream"], "stdout");
+
+        assert_eq!(decoder.push_line("2020-01-01T10:00:03Z stdout P Shutting "), None);
+        assert_eq!(decoder.push_line("2020-01-01T10:00:04Z stderr P to connect"), None);
+        let lines = decoder.flush();
+        assert_eq!(
+            lines.iter().map(|line| line.text.as_str()).collect::<Vec<_>>(),
+            vec!["ERROR Failed to connect", "Shutting "]
+        );
+        assert_eq!(lines[0].fields["stream"], "stderr");
+        assert!(decoder.flush().is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Parses a JSON object into fields. Nested objects are flattened to dotted paths, e.g. `{"http": {"status": 200}}`
+/// gives the `http.status` field. Arrays are kept as JSON text.
+pub fn parse_fields(line: &str) -> Option<HashMap<String, String>> {
+    match serde_json::from_str(line) {
+        Ok(Value::Object(object)) => {
+            let mut fields = HashMap::new();
+            flatten_object(None, object, &mut fields);
+            Some(fields)
+        }
+        _ => None,
+    }
+}
+
+fn flatten_object(path: Option<&str>, object: Map<String, Value>, fields: &mut HashMap<String, String>) {
+    for (key, value) in object {
+        let key = match path {
+            Some(path) => format!("{}.{}", path, key),
+            None => key,
+        };
+        match value {
+            Value::Object(object) => flatten_object(Some(&key), object, fields),
+            Value::String(value) => {
+                fields.insert(key, value);
+            }
+            value => {
+                fields.insert(key, value.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_fields;
+
+    #[test]
+    fn test_parse_json_fields() {
+        let fields = parse_fields(
+            r#"{"ts":"2020-01-01T10:00:00Z","level":"INFO","msg":"Start","http":{"status":200,"ok":true},"tags":["a"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(fields["ts"], "2020-01-01T10:00:00Z");
+        assert_eq!(fields["level"], "INFO");
+        assert_eq!(fields["msg"], "Start");
+        assert_eq!(fields["http.status"], "200");
+        assert_eq!(fields["http.ok"], "true");
+        assert_eq!(fields["tags"], r#"["a"]"#);
+
+        assert_eq!(parse_fields("2020-01-01 10:00:00 INFO Start of log file"), None);
+        assert_eq!(parse_fields("[1, 2]"), None);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, iter::Peekable, str::Chars};
+
+/// Parses a logfmt line, e.g. `level=info msg="Request handled" duration=12ms`. Values can be quoted with double
+/// quotes, which may contain `\"`, `\\`, `\n` and `\t` escapes. Keys without a value get an empty value. Returns `None`
+/// if the line has no `key=value` pair or it has an unterminated quoted value.
+pub fn parse_fields(line: &str) -> Option<HashMap<String, String>> {
+    let mut fields = HashMap::new();
+    let mut has_value = false;
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) {
+            key.push(c);
+        }
+
+        let value = if chars.next_if_eq(&'=').is_some() {
+            has_value = true;
+            if chars.next_if_eq(&'"').is_some() {
+                parse_quoted_value(&mut chars)?
+            } else {
+                let mut value = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    value.push(c);
+                }
+                value
+            }
+        } else {
+            String::new()
+        };
+
+        if !key.is_empty() {
+            fields.insert(key, value);
+        }
+    }
+
+    if has_value {
+        Some(fields)
+    } else {
+        None
+    }
+}
+
+/// Parses a quoted value after its opening quote up to and including the closing quote
+fn parse_quoted_value(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_fields;
+
+    #[test]
+    fn test_parse_logfmt_fields() {
+        let fields = parse_fields(
+            r#"ts=2020-01-01T10:00:00Z level=error msg="Failed to \"start\" app" path=C:\\app\\ debug err="""#,
+        )
+        .unwrap();
+
+        assert_eq!(fields["ts"], "2020-01-01T10:00:00Z");
+        assert_eq!(fields["level"], "error");
+        assert_eq!(fields["msg"], r#"Failed to "start" app"#);
+        assert_eq!(fields["path"], r"C:\\app\\");
+        assert_eq!(fields["debug"], "");
+        assert_eq!(fields["err"], "");
+        assert_eq!(fields.len(), 6);
+
+        assert_eq!(parse_fields("2020-01-01 10:00:00 INFO Start of log file"), None);
+        assert_eq!(parse_fields(r#"msg="unterminated"#), None);
+    }
+}
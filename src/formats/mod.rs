@@ -0,0 +1,152 @@
+use access::{AccessLogFormat, COMBINED_LOG_FORMAT, COMMON_LOG_FORMAT};
+pub use container::{ContainerDecoder, ContainerFormat};
+use std::{collections::HashMap, str::FromStr};
+
+mod access;
+mod container;
+mod json;
+mod logfmt;
+mod syslog;
+
+/// The format of the input lines, which determines how the fields of a record are extracted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum InputFormat {
+    /// Plain text. Fields are extracted from the named capture groups of the prefix
+    #[default]
+    Text,
+    /// JSON Lines. Every line is a JSON object, nested keys are flattened to dotted paths
+    Json,
+    /// logfmt, e.g. `level=info msg="Request handled"`
+    Logfmt,
+    /// Syslog messages in RFC 5424 or RFC 3164 format, with or without the priority
+    Syslog,
+    /// Web server access logs in the Common or Combined Log Format, or in a custom nginx `log_format`
+    AccessLog(AccessLogFormat),
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(InputFormat::Text),
+            "json" => Ok(InputFormat::Json),
+            "logfmt" => Ok(InputFormat::Logfmt),
+            "syslog" => Ok(InputFormat::Syslog),
+            "common" => Ok(InputFormat::AccessLog(AccessLogFormat::new(COMMON_LOG_FORMAT).unwrap())),
+            "combined" => Ok(InputFormat::AccessLog(AccessLogFormat::new(COMBINED_LOG_FORMAT).unwrap())),
+            format if format.contains('$') => AccessLogFormat::new(format)
+                .map(InputFormat::AccessLog)
+                .map_err(|err| format!("Invalid log format: {} ({})", format, err)),
+            format => Err(format!("Unknown input format: {}", format)),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Extracts the fields of a line. Returns `None` for text input and for lines that are not in the expected format
+    pub fn parse_fields(&self, line: &str) -> Option<HashMap<String, String>> {
+        match self {
+            InputFormat::Text => None,
+            InputFormat::Json => json::parse_fields(line),
+            InputFormat::Logfmt => logfmt::parse_fields(line),
+            InputFormat::Syslog => syslog::parse_fields(line),
+            InputFormat::AccessLog(format) => format.parse_fields(line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+/// Renders the fields of a record as text, e.g. `{ts} {level} {msg}`. Fields are referenced by their name between
+/// braces and missing fields are rendered as empty strings. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<TemplatePart>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("Unclosed '{{' in template: {}", value));
+                    }
+                    if name.is_empty() {
+                        return Err(format!("Empty field name in template: {}", value));
+                    }
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Field(name));
+                }
+                '}' => return Err(format!("Unmatched '}}' in template: {}", value)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+impl Template {
+    pub fn render(&self, fields: &HashMap<String, String>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.as_str(),
+                TemplatePart::Field(name) => fields.get(name).map(|value| value.as_str()).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Template;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_render_template() {
+        let template = "{ts} [{level}] {{{msg}}} {missing}".parse::<Template>().unwrap();
+        let fields = vec![("ts", "2020-01-01 10:00:00"), ("level", "INFO"), ("msg", "Start of log file")]
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(template.render(&fields), "2020-01-01 10:00:00 [INFO] {Start of log file} ");
+        assert!("{level".parse::<Template>().is_err());
+        assert!("{}".parse::<Template>().is_err());
+        assert!("level}".parse::<Template>().is_err());
+    }
+}
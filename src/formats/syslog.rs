@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp", "ntp",
+    "security", "console", "clock", "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+];
+
+const SEVERITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+/// The value of fields that are not present in RFC 5424 messages
+const NIL_VALUE: &str = "-";
+
+/// Parses a syslog message in RFC 5424 or RFC 3164 (BSD) format. The priority is optional, so the lines of log files
+/// written by syslog daemons, e.g. `/var/log/syslog`, are parsed as well. The extracted fields are `pri`, `facility`,
+/// `severity`, `version`, `timestamp`, `hostname`, `app_name`, `procid`, `msgid`, `structured_data` and `message`.
+/// The parameters of structured data elements are extracted as `<SD-ID>.<PARAM-NAME>` fields. Fields that are not
+/// present in the message are omitted.
+pub fn parse_fields(line: &str) -> Option<HashMap<String, String>> {
+    let mut fields = HashMap::new();
+    let rest = parse_priority(line, &mut fields)?;
+    match split_token(rest) {
+        (version, Some(rest)) if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) => {
+            fields.insert("version".to_owned(), version.to_owned());
+            parse_rfc5424(rest, &mut fields)?;
+        }
+        _ => parse_rfc3164(rest, &mut fields)?,
+    }
+    Some(fields)
+}
+
+/// Parses the optional `<PRI>` part. Returns the rest of the line.
+fn parse_priority<'a>(line: &'a str, fields: &mut HashMap<String, String>) -> Option<&'a str> {
+    let rest = match line.strip_prefix('<') {
+        Some(rest) => rest,
+        None => return Some(line),
+    };
+    let end = rest.find('>')?;
+    let pri = rest[..end].parse::<usize>().ok()?;
+    let facility = FACILITIES.get(pri / 8)?;
+    fields.insert("pri".to_owned(), pri.to_string());
+    fields.insert("facility".to_owned(), (*facility).to_owned());
+    fields.insert("severity".to_owned(), SEVERITIES[pri % 8].to_owned());
+    Some(&rest[end + 1..])
+}
+
+/// Parses `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]`
+fn parse_rfc5424(text: &str, fields: &mut HashMap<String, String>) -> Option<()> {
+    let mut rest = text;
+    for name in &["timestamp", "hostname", "app_name", "procid", "msgid"] {
+        let (value, remainder) = split_token(rest);
+        rest = remainder?;
+        if value.is_empty() {
+            return None;
+        }
+        if value != NIL_VALUE {
+            fields.insert((*name).to_owned(), value.to_owned());
+        }
+    }
+
+    let message = match rest.strip_prefix(NIL_VALUE) {
+        Some(rest) => rest.strip_prefix(' '),
+        None => {
+            let end = parse_structured_data(rest, fields)?;
+            fields.insert("structured_data".to_owned(), rest[..end].to_owned());
+            rest[end..].strip_prefix(' ')
+        }
+    };
+    if let Some(message) = message {
+        // The message may start with a byte order mark if it's UTF-8
+        fields.insert("message".to_owned(), message.trim_start_matches('\u{feff}').to_owned());
+    }
+    Some(())
+}
+
+/// Parses the structured data elements, e.g. `[exampleSDID@32473 iut="3" eventSource="Application"]`. Returns the
+/// length of the structured data.
+fn parse_structured_data(text: &str, fields: &mut HashMap<String, String>) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, '[')) = chars.peek() {
+        chars.next();
+        let mut id = String::new();
+        while let Some((_, c)) = chars.next_if(|&(_, c)| c != ' ' && c != ']') {
+            id.push(c);
+        }
+
+        loop {
+            match chars.next()? {
+                (_, ']') => break,
+                (_, ' ') => {
+                    let mut name = String::new();
+                    while let Some((_, c)) = chars.next_if(|&(_, c)| c != '=') {
+                        name.push(c);
+                    }
+                    if chars.next()?.1 != '=' || chars.next()?.1 != '"' {
+                        return None;
+                    }
+                    let mut value = String::new();
+                    loop {
+                        match chars.next()?.1 {
+                            '"' => break,
+                            '\\' => value.push(chars.next()?.1),
+                            c => value.push(c),
+                        }
+                    }
+                    fields.insert(format!("{}.{}", id, name), value);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    match chars.peek() {
+        Some(&(end, _)) if end > 0 => Some(end),
+        Some(_) => None,
+        None => Some(text.len()),
+    }
+}
+
+/// Parses `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`
+fn parse_rfc3164(text: &str, fields: &mut HashMap<String, String>) -> Option<()> {
+    // The timestamp has a fixed width, the day of the month is padded with a space
+    let timestamp = text.get(..15)?;
+    let bytes = timestamp.as_bytes();
+    if !bytes[..3].iter().all(u8::is_ascii_alphabetic) || bytes[3] != b' ' || bytes[9] != b':' || bytes[12] != b':' {
+        return None;
+    }
+    fields.insert("timestamp".to_owned(), timestamp.to_owned());
+
+    let (hostname, rest) = split_token(text[15..].trim_start());
+    fields.insert("hostname".to_owned(), hostname.to_owned());
+    let rest = rest.unwrap_or_default();
+
+    let message = match rest.find(": ") {
+        Some(end) if !rest[..end].contains(' ') => {
+            let tag = &rest[..end];
+            match tag.find('[') {
+                Some(start) if tag.ends_with(']') => {
+                    fields.insert("app_name".to_owned(), tag[..start].to_owned());
+                    fields.insert("procid".to_owned(), tag[start + 1..tag.len() - 1].to_owned());
+                }
+                _ => {
+                    fields.insert("app_name".to_owned(), tag.to_owned());
+                }
+            }
+            &rest[end + 2..]
+        }
+        _ => rest,
+    };
+    fields.insert("message".to_owned(), message.to_owned());
+    Some(())
+}
+
+/// Splits the text at the first space. The rest is `None` if there is no space.
+fn split_token(text: &str) -> (&str, Option<&str>) {
+    match text.find(' ') {
+        Some(end) => (&text[..end], Some(&text[end + 1..])),
+        None => (text, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_fields;
+
+    #[test]
+    fn test_parse_rfc5424_fields() {
+        let fields = parse_fields(concat!(
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 "#,
+            r#"[exampleSDID@32473 iut="3" eventSource="Application \"x\""][examplePriority@32473 class="high"] "#,
+            "An application event",
+        ))
+        .unwrap();
+
+        assert_eq!(fields["pri"], "165");
+        assert_eq!(fields["facility"], "local4");
+        assert_eq!(fields["severity"], "notice");
+        assert_eq!(fields["version"], "1");
+        assert_eq!(fields["timestamp"], "2003-10-11T22:14:15.003Z");
+        assert_eq!(fields["hostname"], "mymachine.example.com");
+        assert_eq!(fields["app_name"], "evntslog");
+        assert!(!fields.contains_key("procid"));
+        assert_eq!(fields["msgid"], "ID47");
+        assert_eq!(fields["exampleSDID@32473.iut"], "3");
+        assert_eq!(fields["exampleSDID@32473.eventSource"], r#"Application "x""#);
+        assert_eq!(fields["examplePriority@32473.class"], "high");
+        assert!(fields["structured_data"].ends_with(r#"class="high"]"#));
+        assert_eq!(fields["message"], "An application event");
+
+        let fields = parse_fields("<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su 123 - -").unwrap();
+        assert_eq!(fields["severity"], "crit");
+        assert_eq!(fields["procid"], "123");
+        assert!(!fields.contains_key("structured_data"));
+        assert!(!fields.contains_key("message"));
+    }
+
+    #[test]
+    fn test_parse_rfc3164_fields() {
+        let fields = parse_fields("<34>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick on /dev/pts/8").unwrap();
+        assert_eq!(fields["facility"], "auth");
+        assert_eq!(fields["severity"], "crit");
+        assert_eq!(fields["timestamp"], "Oct 11 22:14:15");
+        assert_eq!(fields["hostname"], "mymachine");
+        assert_eq!(fields["app_name"], "su");
+        assert_eq!(fields["message"], "'su root' failed for lonvick on /dev/pts/8");
+
+        let fields = parse_fields("Jan  1 10:00:00 host CRON[1234]: (root) CMD (run-parts /etc/cron.hourly)").unwrap();
+        assert!(!fields.contains_key("pri"));
+        assert_eq!(fields["timestamp"], "Jan  1 10:00:00");
+        assert_eq!(fields["app_name"], "CRON");
+        assert_eq!(fields["procid"], "1234");
+        assert_eq!(fields["message"], "(root) CMD (run-parts /etc/cron.hourly)");
+
+        assert_eq!(parse_fields("2020-01-01 10:00:00 INFO Start of log file"), None);
+        assert_eq!(parse_fields("<999>Jan  1 10:00:00 host app: message"), None);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::GrokError;
+use std::collections::{HashMap, HashSet};
+
+/// Built-in patterns, adapted from the Logstash grok patterns
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("USERNAME", r"[a-zA-Z0-9._-]+"),
+    ("USER", r"%{USERNAME}"),
+    ("INT", r"[+-]?[0-9]+"),
+    ("BASE10NUM", r"[+-]?(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+)"),
+    ("NUMBER", r"%{BASE10NUM}"),
+    ("BASE16NUM", r"[+-]?(?:0x)?[0-9A-Fa-f]+"),
+    ("POSINT", r"\b[1-9][0-9]*\b"),
+    ("NONNEGINT", r"\b[0-9]+\b"),
+    ("WORD", r"\b\w+\b"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#),
+    ("UUID", r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}"),
+    (
+        "IPV4",
+        r"(?:(?:25[0-5]|2[0-4][0-9]|1?[0-9]{1,2})\.){3}(?:25[0-5]|2[0-4][0-9]|1?[0-9]{1,2})",
+    ),
+    ("IPV6", r"(?:[0-9A-Fa-f]{0,4}:){2,7}[0-9A-Fa-f]{0,4}"),
+    ("IP", r"%{IPV6}|%{IPV4}"),
+    (
+        "HOSTNAME",
+        r"\b[0-9A-Za-z][0-9A-Za-z-]{0,62}(?:\.[0-9A-Za-z][0-9A-Za-z-]{0,62})*\.?\b",
+    ),
+    ("IPORHOST", r"%{IP}|%{HOSTNAME}"),
+    ("HOSTPORT", r"%{IPORHOST}:%{POSINT}"),
+    ("PATH", r"(?:/[^/\s]*)+"),
+    ("URIPATH", r"(?:/[A-Za-z0-9$.+!*'(){},~:;=@#%&_\-]*)+"),
+    ("URIPARAM", r"\?[A-Za-z0-9$.+!*'|(){},~@#%&/=:;_?\-\[\]<>]*"),
+    ("URIPATHPARAM", r"%{URIPATH}(?:%{URIPARAM})?"),
+    (
+        "MONTH",
+        concat!(
+            r"\b(?:Jan(?:uary)?|Feb(?:ruary)?|Mar(?:ch)?|Apr(?:il)?|May|Jun(?:e)?|Jul(?:y)?|Aug(?:ust)?",
+            r"|Sep(?:tember)?|Oct(?:ober)?|Nov(?:ember)?|Dec(?:ember)?)\b",
+        ),
+    ),
+    ("MONTHNUM", r"0?[1-9]|1[0-2]"),
+    ("MONTHDAY", r"0[1-9]|[12][0-9]|3[01]|[1-9]"),
+    (
+        "DAY",
+        r"Mon(?:day)?|Tue(?:sday)?|Wed(?:nesday)?|Thu(?:rsday)?|Fri(?:day)?|Sat(?:urday)?|Sun(?:day)?",
+    ),
+    ("YEAR", r"(?:\d\d){1,2}"),
+    ("HOUR", r"2[0123]|[01]?[0-9]"),
+    ("MINUTE", r"[0-5][0-9]"),
+    ("SECOND", r"(?:[0-5]?[0-9]|60)(?:[:.,][0-9]+)?"),
+    ("TIME", r"%{HOUR}:%{MINUTE}(?::%{SECOND})?"),
+    ("DATE_US", r"%{MONTHNUM}[/-]%{MONTHDAY}[/-]%{YEAR}"),
+    ("DATE_EU", r"%{MONTHDAY}[./-]%{MONTHNUM}[./-]%{YEAR}"),
+    ("DATE", r"%{DATE_US}|%{DATE_EU}"),
+    ("DATESTAMP", r"%{DATE}[- ]%{TIME}"),
+    ("ISO8601_TIMEZONE", r"Z|[+-]%{HOUR}(?::?%{MINUTE})"),
+    (
+        "TIMESTAMP_ISO8601",
+        r"%{YEAR}-%{MONTHNUM}-%{MONTHDAY}[T ]%{HOUR}:?%{MINUTE}(?::?%{SECOND})?%{ISO8601_TIMEZONE}?",
+    ),
+    ("SYSLOGTIMESTAMP", r"%{MONTH} +%{MONTHDAY} %{TIME}"),
+    ("HTTPDATE", r"%{MONTHDAY}/%{MONTH}/%{YEAR}:%{TIME} %{INT}"),
+    (
+        "LOGLEVEL",
+        concat!(
+            r"[Aa]lert|ALERT|[Tt]race|TRACE|[Dd]ebug|DEBUG|[Nn]otice|NOTICE|[Ii]nfo?(?:rmation)?|INFO?(?:RMATION)?",
+            r"|[Ww]arn?(?:ing)?|WARN?(?:ING)?|[Ee]rr?(?:or)?|ERR?(?:OR)?|[Cc]rit?(?:ical)?|CRIT?(?:ICAL)?",
+            r"|[Ff]atal|FATAL|[Ss]evere|SEVERE|EMERG(?:ENCY)?|[Ee]merg(?:ency)?",
+        ),
+    ),
+    ("PROG", r"[\x21-\x5a\x5c\x5e-\x7e]+"),
+    ("SYSLOGPROG", r"%{PROG:program}(?:\[%{POSINT:pid}\])?"),
+    ("SYSLOGHOST", r"%{IPORHOST}"),
+];
+
+/// Patterns can't reference each other deeper than this, which also stops recursive patterns
+const MAX_DEPTH: usize = 32;
+
+/// Expands Logstash-style grok references in patterns, e.g. `%{TIMESTAMP_ISO8601:ts} %{LOGLEVEL:level}`.
+/// `%{NAME}` is replaced by the pattern called `NAME`, `%{NAME:field}` captures it in the `field` named group as well.
+/// A type suffix like `%{NUMBER:bytes:int}` is accepted and ignored. Patterns without references are kept as they are.
+#[derive(Debug, Clone)]
+pub struct Grok {
+    patterns: HashMap<String, String>,
+}
+
+impl Default for Grok {
+    fn default() -> Self {
+        Self {
+            patterns: BUILTIN_PATTERNS
+                .iter()
+                .map(|(name, pattern)| ((*name).to_owned(), (*pattern).to_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl Grok {
+    /// Adds user-defined patterns. They can reference each other and the built-in patterns, and override them.
+    pub fn with_patterns<I: IntoIterator<Item = (String, String)>>(mut self, patterns: I) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// Fails if a capture name is used twice, e.g. by referencing a pattern with a named capture twice, since the
+    /// regex couldn't be compiled
+    pub fn expand(&self, pattern: &str) -> Result<String, GrokError> {
+        self.expand_with_depth(pattern, pattern, 0, &mut HashSet::new())
+    }
+
+    /// `pattern_name` is the name of the expanded pattern, which is reported if it repeats a capture name
+    fn expand_with_depth(
+        &self,
+        pattern: &str,
+        pattern_name: &str,
+        depth: usize,
+        capture_names: &mut HashSet<String>,
+    ) -> Result<String, GrokError> {
+        let mut expanded = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+        while let Some(start) = rest.find("%{") {
+            add_capture_names(&rest[..start], pattern_name, capture_names)?;
+            expanded.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| GrokError::Syntax(pattern.to_owned()))?;
+
+            let mut parts = rest[start + 2..end].splitn(3, ':');
+            let name = parts.next().unwrap_or_default();
+            let field = parts.next();
+            let referenced = self
+                .patterns
+                .get(name)
+                .ok_or_else(|| GrokError::UnknownPattern(name.to_owned()))?;
+            if depth >= MAX_DEPTH {
+                return Err(GrokError::Recursive(name.to_owned()));
+            }
+            if let Some(field) = field {
+                add_capture_name(field, pattern_name, capture_names)?;
+            }
+            let referenced = self.expand_with_depth(referenced, name, depth + 1, capture_names)?;
+            match field {
+                Some(field) => expanded.push_str(&format!("(?P<{}>{})", field, referenced)),
+                None => expanded.push_str(&format!("(?:{})", referenced)),
+            }
+
+            rest = &rest[end + 1..];
+        }
+        add_capture_names(rest, pattern_name, capture_names)?;
+        expanded.push_str(rest);
+
+        Ok(expanded)
+    }
+}
+
+/// Adds the names of the named groups written in the regex, e.g. `(?P<id>\d+)`
+fn add_capture_names(regex: &str, pattern_name: &str, capture_names: &mut HashSet<String>) -> Result<(), GrokError> {
+    let mut rest = regex;
+    while let Some(start) = rest.find("(?P<") {
+        rest = &rest[start + 4..];
+        if let Some(end) = rest.find('>') {
+            add_capture_name(&rest[..end], pattern_name, capture_names)?;
+            rest = &rest[end + 1..];
+        }
+    }
+    Ok(())
+}
+
+fn add_capture_name(name: &str, pattern_name: &str, capture_names: &mut HashSet<String>) -> Result<(), GrokError> {
+    if capture_names.insert(name.to_owned()) {
+        Ok(())
+    } else {
+        Err(GrokError::DuplicateCapture(name.to_owned(), pattern_name.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Grok;
+    use crate::error::GrokError;
+    use regex::Regex;
+
+    #[test]
+    fn test_expand_grok_patterns() {
+        let grok = Grok::default();
+        assert_eq!(grok.expand(r"[\d]{4} INFO ").unwrap(), r"[\d]{4} INFO ");
+
+        let regex = Regex::new(
+            &grok
+                .expand("^%{TIMESTAMP_ISO8601:ts} %{LOGLEVEL:level} %{GREEDYDATA:message}")
+                .unwrap(),
+        )
+        .unwrap();
+        let captures = regex
+            .captures("2020-01-01 10:00:50 ERROR Failed to start application")
+            .unwrap();
+        assert_eq!(&captures["ts"], "2020-01-01 10:00:50");
+        assert_eq!(&captures["level"], "ERROR");
+        assert_eq!(&captures["message"], "Failed to start application");
+        assert_eq!(regex.capture_names().flatten().count(), 3);
+
+        let regex = Regex::new(&grok.expand("%{IPORHOST:client} %{NUMBER:bytes:int}").unwrap()).unwrap();
+        let captures = regex.captures("10.0.0.1 512").unwrap();
+        assert_eq!(&captures["client"], "10.0.0.1");
+        assert_eq!(&captures["bytes"], "512");
+
+        let grok = Grok::default().with_patterns(vec![
+            ("STATE".to_owned(), r"%{WORD}(?:_%{WORD})*".to_owned()),
+            ("LOOP".to_owned(), "%{LOOP}".to_owned()),
+        ]);
+        let regex = Regex::new(&grok.expand("Set state to %{STATE:state}").unwrap()).unwrap();
+        assert_eq!(
+            &regex.captures("INFO Set state to main_menu").unwrap()["state"],
+            "main_menu"
+        );
+
+        assert!(grok.expand("%{UNKNOWN}").is_err());
+        assert!(grok.expand("%{LOOP}").is_err());
+        assert!(grok.expand("%{WORD").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_captures() {
+        let grok = Grok::default().with_patterns(vec![("APP".to_owned(), r"(?P<program>\w+):".to_owned())]);
+        assert!(Regex::new(&grok.expand("%{SYSLOGPROG} %{WORD:message}").unwrap()).is_ok());
+
+        for (pattern, name, pattern_name) in &[
+            ("%{SYSLOGPROG} %{SYSLOGPROG}", "program", "SYSLOGPROG"),
+            ("%{WORD:pid} %{SYSLOGPROG}", "pid", "SYSLOGPROG"),
+            ("%{SYSLOGPROG} %{APP}", "program", "APP"),
+            (r"(?P<level>\w+) %{LOGLEVEL:level}", "level", r"(?P<level>\w+) %{LOGLEVEL:level}"),
+        ] {
+            match grok.expand(pattern) {
+                Err(GrokError::DuplicateCapture(duplicate_name, duplicate_pattern_name)) => {
+                    assert_eq!(duplicate_name, *name);
+                    assert_eq!(duplicate_pattern_name, *pattern_name);
+                }
+                result => panic!("Unexpected result for {}: {:?}", pattern, result),
+            }
+        }
+    }
+}
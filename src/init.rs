@@ -0,0 +1,328 @@
+use crate::detect::{DetectedConfig, DetectedEventPatterns, DetectedStatePattern};
+use itertools::Itertools;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+/// Words that start and end an event, e.g. `Mouse left down` and `Mouse left up`
+const EVENT_WORDS: [(&str, &str); 19] = [
+    ("down", "up"),
+    ("start", "stop"),
+    ("started", "stopped"),
+    ("starting", "stopping"),
+    ("begin", "end"),
+    ("open", "close"),
+    ("opened", "closed"),
+    ("opening", "closing"),
+    ("connect", "disconnect"),
+    ("connected", "disconnected"),
+    ("connecting", "disconnecting"),
+    ("enter", "exit"),
+    ("entered", "exited"),
+    ("entering", "exiting"),
+    ("acquire", "release"),
+    ("acquired", "released"),
+    ("lock", "unlock"),
+    ("locked", "unlocked"),
+    ("login", "logout"),
+];
+
+/// Phrases that are followed by the new state, e.g. `Set state to main_menu`
+const STATE_PATTERN: &str = r"(?i)^.*?\b(?:state|status|mode|phase)(?:\s+(?:changed\s+)?(?:to|is)\b|\s*[:=])";
+
+/// Colors of the proposed patterns, which differ from the level colors. The first ones are the colors of
+/// tests/example.json.
+const EVENT_COLORS: [&str; 4] = ["29", "32", "35", "38"];
+const STATE_COLORS: [&str; 4] = ["30", "33", "36", "39"];
+
+/// The number of proposed event and state patterns
+const MAX_PROPOSALS: usize = 5;
+
+/// An event proposed from the messages with the number of times it started
+#[derive(Debug, PartialEq)]
+pub struct ProposedEvent {
+    pub start_pattern: String,
+    pub end_pattern: String,
+    pub count: usize,
+}
+
+/// Builds a config from the detected config by asking the user which levels, events and states to keep and which
+/// colors they get. The questions are written to `output` and the answers are read from `input`, an empty answer or
+/// the end of the input accepts the proposal.
+pub fn init<R: BufRead, W: Write>(
+    mut detected: DetectedConfig,
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<DetectedConfig> {
+    let mut prompt = Prompt { input, output };
+
+    let mut pattern_colors = Vec::new();
+    for mut pattern_color in detected.pattern_colors.drain(..) {
+        let question = format!("Color of lines matching {}", pattern_color.pattern);
+        if let Some(color) = prompt.ask_color(&question, &pattern_color.color)? {
+            pattern_color.color = color;
+            pattern_colors.push(pattern_color);
+        }
+    }
+    detected.pattern_colors = pattern_colors;
+
+    let messages = detected
+        .messages
+        .iter()
+        .map(|message| message.as_str())
+        .collect::<Vec<_>>();
+    let pattern_of = |text: &str| match detected.message_field {
+        Some(_) => format!("^{}", regex::escape(text)),
+        None => regex::escape(text),
+    };
+
+    let mut event_patterns = Vec::new();
+    for event in propose_events(&messages) {
+        let question = format!(
+            "Add an event from \"{}\" to \"{}\" ({} times)?",
+            event.start_pattern, event.end_pattern, event.count
+        );
+        if prompt.confirm(&question)? {
+            let default_color = EVENT_COLORS[event_patterns.len() % EVENT_COLORS.len()];
+            if let Some(color) = prompt.ask_color("Color of the event", default_color)? {
+                event_patterns.push(DetectedEventPatterns {
+                    field: detected.message_field.clone(),
+                    start_pattern: pattern_of(&event.start_pattern),
+                    end_pattern: pattern_of(&event.end_pattern),
+                    color,
+                });
+            }
+        }
+    }
+
+    let mut state_patterns = Vec::new();
+    for (state, count) in propose_states(&messages) {
+        if prompt.confirm(&format!("Add a state pattern \"{}\" ({} times)?", state, count))? {
+            let default_color = STATE_COLORS[state_patterns.len() % STATE_COLORS.len()];
+            if let Some(color) = prompt.ask_color("Color of the state", default_color)? {
+                state_patterns.push(DetectedStatePattern {
+                    field: detected.message_field.clone(),
+                    pattern: pattern_of(&state),
+                    color,
+                });
+            }
+        }
+    }
+
+    detected.event_patterns = event_patterns;
+    detected.state_patterns = state_patterns;
+    Ok(detected)
+}
+
+/// Finds pairs of frequent messages that only differ by a start and an end word after the same words, e.g.
+/// `INFO Mouse left down at 0, 0` and `INFO Mouse left up at 10, 0`. The patterns end with the start and end words.
+pub fn propose_events(messages: &[&str]) -> Vec<ProposedEvent> {
+    let templates = count_templates(messages);
+    let mut events = HashMap::<(String, String), (usize, usize)>::new();
+    for (template, count) in &templates {
+        for (index, word) in template.iter().enumerate() {
+            let lowercase_word = word.to_lowercase();
+            for (start_word, end_word) in &EVENT_WORDS {
+                let is_start = lowercase_word == *start_word;
+                if !is_start && lowercase_word != *end_word {
+                    continue;
+                }
+                let pattern_with = |event_word: &str| {
+                    let mut words = template[..index]
+                        .iter()
+                        .map(|word| (*word).to_owned())
+                        .collect::<Vec<_>>();
+                    words.push(match_case(word, event_word));
+                    words.join(" ")
+                };
+                let counts = events
+                    .entry((pattern_with(start_word), pattern_with(end_word)))
+                    .or_default();
+                if is_start {
+                    counts.0 += count;
+                } else {
+                    counts.1 += count;
+                }
+            }
+        }
+    }
+
+    events
+        .into_iter()
+        .filter(|(_, (start_count, end_count))| *start_count > 0 && *end_count > 0)
+        .map(|((start_pattern, end_pattern), (count, _))| ProposedEvent {
+            start_pattern,
+            end_pattern,
+            count,
+        })
+        .sorted_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.start_pattern.cmp(&b.start_pattern))
+        })
+        .take(MAX_PROPOSALS)
+        .collect()
+}
+
+/// Finds the phrases that introduce a new state, e.g. `INFO Set state to`, with the number of messages containing
+/// them
+pub fn propose_states(messages: &[&str]) -> Vec<(String, usize)> {
+    let state_regex = Regex::new(STATE_PATTERN).unwrap();
+    let mut states = HashMap::<String, usize>::new();
+    for message in messages {
+        if let Some(state) = state_regex.find(message) {
+            *states.entry(state.as_str().to_owned()).or_default() += 1;
+        }
+    }
+
+    states
+        .into_iter()
+        .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+        .take(MAX_PROPOSALS)
+        .collect()
+}
+
+/// Reduces the messages to their leading words, which are the same for every message of a kind. The first word
+/// containing something else than letters is usually a value, e.g. a number or an identifier, and ends the template.
+fn count_templates<'a>(messages: &[&'a str]) -> HashMap<Vec<&'a str>, usize> {
+    let mut templates = HashMap::new();
+    for message in messages {
+        let template = message
+            .split_whitespace()
+            .take_while(|word| word.chars().all(|c| c.is_alphabetic() || c == '_'))
+            .collect::<Vec<_>>();
+        if !template.is_empty() {
+            *templates.entry(template).or_insert(0) += 1;
+        }
+    }
+    templates
+}
+
+/// Writes the event word like the word of the message, e.g. `Down` gives `Up` and `DOWN` gives `UP`
+fn match_case(word: &str, event_word: &str) -> String {
+    if word.chars().all(|c| c.is_uppercase()) {
+        event_word.to_uppercase()
+    } else if word.chars().next().is_some_and(char::is_uppercase) {
+        let mut chars = event_word.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        event_word.to_owned()
+    }
+}
+
+struct Prompt<'a, R, W> {
+    input: &'a mut R,
+    output: &'a mut W,
+}
+
+impl<'a, R: BufRead, W: Write> Prompt<'a, R, W> {
+    /// Returns the trimmed answer, which is empty at the end of the input
+    fn ask(&mut self, question: &str) -> io::Result<String> {
+        write!(self.output, "{} ", question)?;
+        self.output.flush()?;
+        let mut answer = String::new();
+        if self.input.read_line(&mut answer)? == 0 {
+            writeln!(self.output)?;
+        }
+        Ok(answer.trim().to_owned())
+    }
+
+    fn confirm(&mut self, question: &str) -> io::Result<bool> {
+        loop {
+            match self.ask(&format!("{} [Y/n]", question))?.to_lowercase().as_str() {
+                "" | "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "Please answer y or n")?,
+            }
+        }
+    }
+
+    /// Asks for a color from 0 to 255. Returns `None` if the user answers `-` to leave the pattern out.
+    fn ask_color(&mut self, question: &str, default_color: &str) -> io::Result<Option<String>> {
+        loop {
+            let answer = self.ask(&format!("{} (0-255, - to leave out) [{}]", question, default_color))?;
+            match answer.as_str() {
+                "" => return Ok(Some(default_color.to_owned())),
+                "-" => return Ok(None),
+                color if color.parse::<u8>().is_ok() => return Ok(Some(color.to_owned())),
+                _ => writeln!(self.output, "Please enter a number from 0 to 255")?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{init, propose_events, propose_states, ProposedEvent};
+    use crate::{config::Config, detect::detect};
+    use std::{
+        fs::File,
+        io::{self, BufRead, BufReader},
+        path::PathBuf,
+    };
+
+    #[test]
+    fn test_propose_events_and_states() {
+        let messages = [
+            "INFO Mouse left down at 0, 0",
+            "INFO Mouse left up at 10, 0",
+            "INFO Mouse left down at 10, 0",
+            "INFO Connection opened",
+            "INFO Set state to main_menu",
+            "DEBUG Mode: fullscreen",
+        ];
+        assert_eq!(
+            propose_events(&messages),
+            vec![ProposedEvent {
+                start_pattern: "INFO Mouse left down".to_owned(),
+                end_pattern: "INFO Mouse left up".to_owned(),
+                count: 2,
+            }]
+        );
+        assert_eq!(
+            propose_states(&messages),
+            vec![("DEBUG Mode:".to_owned(), 1), ("INFO Set state to".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_init_config() {
+        let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
+        let lines = BufReader::new(File::open(test_log_path).unwrap())
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // The defaults give tests/example.json
+        let config = init(detect(&lines), &mut io::empty(), &mut io::sink()).unwrap();
+        let expected_config = serde_json::from_str::<serde_json::Value>(include_str!("../tests/example.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&config.to_json().unwrap()).unwrap(),
+            serde_json::json!({
+                "timestamp": { "format": "iso8601" },
+                "prefix": expected_config["prefix"],
+                "pattern_colors": expected_config["pattern_colors"],
+                "event_patterns": expected_config["event_patterns"],
+                "state_patterns": expected_config["state_patterns"],
+            })
+        );
+
+        let mut output = Vec::new();
+        let config = init(detect(&lines), &mut "\n-\n196\nx\nn\n\n".as_bytes(), &mut output).unwrap();
+        let patterns = config
+            .pattern_colors
+            .iter()
+            .map(|pattern_color| (pattern_color.pattern.as_str(), pattern_color.color.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(patterns, vec![("INFO", "28"), ("ERROR", "196")]);
+        assert!(config.event_patterns.is_empty());
+        assert_eq!(config.state_patterns.len(), 1);
+        assert!(String::from_utf8(output).unwrap().contains("Please answer y or n"));
+        assert!(Config::from_json_str(config.to_json().unwrap()).is_ok());
+    }
+}
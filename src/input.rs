@@ -0,0 +1,173 @@
+use crate::{
+    error::InputError,
+    record::RecordParser,
+    seek::find_offset,
+    timestamp::Timestamp,
+};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Path value that refers to the standard input instead of a file
+pub const STDIN_PATH: &str = "-";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression format from the magic bytes at the start of the data. The bzip2 magic is followed by
+    /// the block size from 1 to 9, so text starting with `BZh` isn't mistaken for bzip2.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if let [b'B', b'Z', b'h', b'1'..=b'9', ..] = header {
+            Some(Compression::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Opens the input file or the standard input. Compressed input is decompressed transparently.
+pub fn open_input(path: &str) -> io::Result<Box<dyn BufRead>> {
+    match path {
+        STDIN_PATH => decompress(BufReader::new(io::stdin())),
+        path => decompress(BufReader::new(File::open(Path::new(path))?)),
+    }
+}
+
+/// Opens the input and skips the lines before `since`. Uncompressed files are searched with binary search, assuming
+/// that the lines are sorted by their timestamps. Other inputs are read from the beginning, so the skipping is left to
+/// the time filter.
+pub fn open_input_since(path: &str, parser: &RecordParser, since: Timestamp) -> io::Result<Box<dyn BufRead>> {
+    if path == STDIN_PATH {
+        return open_input(path);
+    }
+
+    let mut reader = BufReader::new(File::open(Path::new(path))?);
+    if Compression::detect(reader.fill_buf()?).is_some() {
+        return decompress(reader);
+    }
+
+    // Without timestamps to search for, the time filter gets to decide about every line
+    let offset = find_offset(&mut reader, parser, since)?.unwrap_or(0);
+    reader.seek(SeekFrom::Start(offset))?;
+    Ok(Box::new(reader))
+}
+
+fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    let compression = Compression::detect(reader.fill_buf()?);
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))),
+        Some(Compression::Bzip2) => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader))),
+        Some(Compression::Xz) => Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+/// Expands glob patterns in the input paths. Paths without glob characters are kept as they are, even if they don't
+/// exist, so opening them reports a proper error later. The standard input is used if no path is given.
+pub fn expand_input_paths<'a, I>(values: I) -> Result<Vec<String>, InputError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut paths = Vec::new();
+    for value in values {
+        if !is_glob_pattern(value) {
+            paths.push(value.to_owned());
+            continue;
+        }
+
+        let matched_paths = glob::glob(value)
+            .map_err(|err| InputError::Pattern(value.to_owned(), err))?
+            .map(|entry| entry.map(|path| path.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if matched_paths.is_empty() {
+            return Err(InputError::NoMatch(value.to_owned()));
+        }
+        paths.extend(matched_paths);
+    }
+
+    if paths.is_empty() {
+        paths.push(STDIN_PATH.to_owned());
+    }
+
+    Ok(paths)
+}
+
+fn is_glob_pattern(value: &str) -> bool {
+    value.contains(['*', '?', '['])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, expand_input_paths, Compression, STDIN_PATH};
+    use crate::error::InputError;
+    use flate2::{write::GzEncoder, Compression as GzCompression};
+    use std::io::{BufRead, Cursor, Write};
+
+    const LOG: &str = "2020-01-01 10:00:00 INFO Start of log file\n2020-01-01 10:01:00 INFO End of log file\n";
+
+    fn read_lines(data: Vec<u8>) -> Vec<String> {
+        decompress(Cursor::new(data)).unwrap().lines().map(|line| line.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_decompress() {
+        let expected_lines = LOG.lines().map(|line| line.to_owned()).collect::<Vec<_>>();
+
+        let mut encoder = GzEncoder::new(Vec::new(), GzCompression::default());
+        encoder.write_all(LOG.as_bytes()).unwrap();
+        let gzip = encoder.finish().unwrap();
+        assert_eq!(Compression::detect(&gzip), Some(Compression::Gzip));
+        assert_eq!(read_lines(gzip), expected_lines);
+
+        let zstd = zstd::encode_all(LOG.as_bytes(), 0).unwrap();
+        assert_eq!(Compression::detect(&zstd), Some(Compression::Zstd));
+        assert_eq!(read_lines(zstd), expected_lines);
+
+        assert_eq!(Compression::detect(LOG.as_bytes()), None);
+        assert_eq!(read_lines(LOG.as_bytes().to_vec()), expected_lines);
+        assert_eq!(Compression::detect(b"BZh9"), Some(Compression::Bzip2));
+        assert_eq!(Compression::detect(b"BZh is not compressed\n"), None);
+    }
+
+    #[test]
+    fn test_decompress_truncated_input() {
+        let mut encoder = GzEncoder::new(Vec::new(), GzCompression::default());
+        encoder.write_all(LOG.as_bytes()).unwrap();
+        let mut gzip = encoder.finish().unwrap();
+        gzip.truncate(gzip.len() / 2);
+        assert!(decompress(Cursor::new(gzip)).unwrap().lines().any(|line| line.is_err()));
+    }
+
+    #[test]
+    fn test_expand_input_paths() {
+        let pattern = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/*.log");
+        let paths = expand_input_paths(vec!["app.log", pattern]).unwrap();
+        assert_eq!(paths, vec![
+            "app.log".to_owned(),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test.log").to_owned(),
+        ]);
+
+        assert_eq!(expand_input_paths(vec![]).unwrap(), vec![STDIN_PATH.to_owned()]);
+
+        let pattern = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/*.missing");
+        match expand_input_paths(vec![pattern]) {
+            Err(InputError::NoMatch(value)) => assert_eq!(value, pattern),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
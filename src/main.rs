@@ -1,24 +1,67 @@
 use ansi_term::Color;
-use clap::{App, Arg, SubCommand, ArgMatches};
-use config::{create_regex_with_prefix, Config};
-use processors::{Colorize, EventPatterns, EventProcessor, PatternColor, Processor, StateProcessor};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::PathBuf,
+use clap::{App, AppSettings, Arg, SubCommand, ArgMatches};
+use config::{create_regex_with_prefix, Config, ConfigFormat};
+use processors::{Colorize, EventPatterns, EventProcessor, FieldCounter, PatternColor, Processor, StateProcessor};
+use regex::Regex;
+use std::{error::Error, fmt::Display, io::{self, BufRead}, path::PathBuf, process};
+use crate::{
+    error::ParseColorError,
+    formats::{ContainerDecoder, ContainerFormat, InputFormat, Template},
+    grok::Grok,
+    input::{expand_input_paths, open_input, open_input_since, STDIN_PATH},
+    pipeline::Pipeline,
+    record::{RecordAssembler, RecordParser},
+    timestamp::{parse_time_bound, TimeFilter, TimeRange, TimestampFormat, TimestampParser},
 };
-use crate::error::ParseColorError;
 
 mod config;
+mod detect;
 mod error;
+mod follow;
+mod formats;
+mod grok;
+mod init;
+mod input;
+mod merge;
+mod pipeline;
 mod processors;
+mod record;
+mod seek;
+mod timestamp;
+
+/// Colors of the source labels when merging inputs
+const SOURCE_COLORS: [u8; 6] = [33, 135, 166, 37, 170, 142];
+
+#[derive(Default)]
+struct Settings {
+    processors: Vec<Box<dyn Processor>>,
+    /// The patterns of the event processors, whose named capture groups are fields of the records
+    event_patterns: Vec<EventPatterns>,
+    prefix: Option<String>,
+    timestamp_parser: Option<TimestampParser>,
+    record_assembler: Option<RecordAssembler>,
+    input_format: InputFormat,
+    template: Option<Template>,
+    container_format: Option<ContainerFormat>,
+}
 
 fn main() {
     let matches = App::new("logan")
         .version("0.1")
         .author("Róbert Kalmár <rfrostkalmar@gmail.com>")
         .about("Log analyzer CLI application")
-        .subcommand(SubCommand::with_name("use-config").arg(Arg::with_name("config_path").required(true)))
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("use-config")
+                .arg(Arg::with_name("config_path").required(true))
+                .arg(
+                    Arg::with_name("config_format")
+                        .long("config-format")
+                        .takes_value(true)
+                        .possible_values(&["json", "yaml", "toml"])
+                        .help("The format of the config file, selected by its extension by default"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("colorize")
                 .arg(Arg::with_name("prefix").short("P").long("prefix").takes_value(true))
@@ -36,6 +79,30 @@ fn main() {
             SubCommand::with_name("events")
                 .arg(Arg::with_name("prefix").short("P").long("prefix").takes_value(true))
                 .arg(Arg::with_name("color").short("c").long("color").takes_value(true))
+                .arg(
+                    Arg::with_name("key")
+                        .short("k")
+                        .long("key")
+                        .takes_value(true)
+                        .help("A named capture group of both patterns that pairs the start and the end of events"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .takes_value(true)
+                        .help("Flushes events that don't end within a number of lines or a duration, e.g. 100 or 30s"),
+                )
+                .arg(
+                    Arg::with_name("max_lines")
+                        .long("max-lines")
+                        .takes_value(true)
+                        .help("Flushes events as truncated once they reach a number of lines"),
+                )
+                .arg(
+                    Arg::with_name("orphans")
+                        .long("orphans")
+                        .help("Lists the end lines without a start at the end of the input"),
+                )
                 .arg(Arg::with_name("start").required(true))
                 .arg(Arg::with_name("end").required(true)),
         )
@@ -45,58 +112,371 @@ fn main() {
                 .arg(Arg::with_name("color").short("c").long("color").takes_value(true))
                 .arg(Arg::with_name("regex").required(true)),
         )
-        .arg(Arg::with_name("INPUT").required(true))
+        .subcommand(
+            SubCommand::with_name("detect")
+                .about("Guesses the format of a log file and prints a config file for it")
+                .arg(Arg::with_name("file").required(true).help("The log file to sample, \"-\" for the standard input"))
+                .arg(
+                    Arg::with_name("lines")
+                        .short("n")
+                        .long("lines")
+                        .takes_value(true)
+                        .default_value("100")
+                        .help("The number of lines to sample"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Asks which levels, events and states of a log file to color and prints a config file for them")
+                .arg(Arg::with_name("file").required(true).help("The log file to sample"))
+                .arg(
+                    Arg::with_name("lines")
+                        .short("n")
+                        .long("lines")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .help("The number of lines to sample"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .short("y")
+                        .long("yes")
+                        .help("Accepts all proposals without asking"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("count")
+                .about("Counts the occurrences of the values of fields")
+                .arg(Arg::with_name("prefix").short("P").long("prefix").takes_value(true))
+                .arg(Arg::with_name("fields").multiple(true).required(true)),
+        )
+        .arg(
+            Arg::with_name("INPUT")
+                .multiple(true)
+                .help("Input log files or glob patterns. Reads the standard input if omitted or \"-\""),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .short("f")
+                .long("follow")
+                .help("Keep reading the input file as it grows, even if it gets rotated")
+                .conflicts_with("merge"),
+        )
+        .arg(
+            Arg::with_name("merge")
+                .short("m")
+                .long("merge")
+                .help("Merge the lines of the input files in chronological order"),
+        )
+        .arg(
+            Arg::with_name("timestamp_format")
+                .short("t")
+                .long("timestamp-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help("Timestamp format: iso8601, rfc3339, syslog, epoch_millis, epoch_seconds or a strftime format"),
+        )
+        .arg(
+            Arg::with_name("since")
+                .long("since")
+                .takes_value(true)
+                .value_name("TIME")
+                .help("Skip lines before this time"),
+        )
+        .arg(
+            Arg::with_name("until")
+                .long("until")
+                .takes_value(true)
+                .value_name("TIME")
+                .help("Skip lines after this time"),
+        )
+        .arg(
+            Arg::with_name("multiline")
+                .long("multiline")
+                .help("Join lines that don't start with the prefix to the preceding line"),
+        )
+        .arg(
+            Arg::with_name("continuation")
+                .long("continuation")
+                .takes_value(true)
+                .value_name("PATTERN")
+                .help("Join lines matching this pattern to the preceding line"),
+        )
+        .arg(
+            Arg::with_name("input_format")
+                .short("i")
+                .long("input-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help("Format of the input lines: text, json, logfmt, syslog, common, combined or an nginx log_format"),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .help("Display structured records with this template, e.g. \"{ts} {level} {msg}\""),
+        )
+        .arg(
+            Arg::with_name("container")
+                .long("container")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help(
+                    "Unwrap the lines of container log files: docker or cri. Container logs are only recognized by \
+                     the detect subcommand, so this or the container key of the config is required to read them",
+                ),
+        )
         .get_matches();
 
-    let input_path = PathBuf::from(matches.value_of("INPUT").unwrap());
-
-    let mut processors = match parse_processors(matches) {
-        Ok(val) => val,
-        Err(err) => {
-            eprintln!("{}", err);
-            return;
+    if let Some(detect_matches) = matches.subcommand_matches("detect") {
+        if let Err(err) = detect_config(detect_matches) {
+            fail(err);
+        }
+        return;
+    }
+    if let Some(init_matches) = matches.subcommand_matches("init") {
+        if let Err(err) = init_config(init_matches) {
+            fail(err);
         }
+        return;
+    }
+
+    let input_paths =
+        expand_input_paths(matches.values_of("INPUT").into_iter().flatten()).unwrap_or_else(|err| fail(err));
+    let follow = matches.is_present("follow");
+    let merge = matches.is_present("merge");
+    let time_range = parse_time_range(&matches).unwrap_or_else(|err| fail(err));
+
+    let mut settings = parse_settings(&matches).unwrap_or_else(|err| fail(err));
+    let mut pipeline = Pipeline::new(std::mem::take(&mut settings.processors));
+    pipeline.set_container_decoder(settings.container_format.map(ContainerDecoder::new));
+    pipeline.set_record_assembler(settings.record_assembler.take());
+    let record_parser = match RecordParser::new(settings.prefix.as_deref(), settings.timestamp_parser.clone()) {
+        Ok(record_parser) => record_parser
+            .with_input_format(std::mem::take(&mut settings.input_format), settings.template.take())
+            .with_container_format(settings.container_format)
+            .with_event_patterns(&settings.event_patterns),
+        Err(err) => fail(format!("Invalid prefix: {}", err)),
     };
+    pipeline.set_record_parser(record_parser.clone());
+
+    if let Some(time_range) = time_range {
+        if settings.timestamp_parser.is_none() {
+            fail("Filtering by time requires a timestamp format");
+        }
+        pipeline.set_time_filter(Some(TimeFilter::new(time_range)));
+    }
 
-    let mut last_process_required_separator = false;
-    let mut has_output = false;
-    let input_file = File::open(input_path).expect("Failed to open input file");
-    let reader = BufReader::new(input_file);
-    for line in reader.lines() {
-        let line = line.expect("Failed to read line from input file");
-        for processor in &mut processors {
-            if let Some(output) = processor.process_line(line.as_str()) {
-                if has_output && (last_process_required_separator || processor.requires_separator()) {
-                    println!("{sep}\n{}", output, sep="-".repeat(50));
-                } else {
-                    println!("{}", output);
-                }
-
-                has_output = true;
-                last_process_required_separator = processor.requires_separator();
+    if follow {
+        let input_path = match input_paths.as_slice() {
+            [path] if path != STDIN_PATH => path,
+            [_] => fail("Following requires an input file"),
+            _ => fail("Following supports a single input file only"),
+        };
+        match follow::follow(input_path.as_ref(), &mut pipeline) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => exit_on_output_error(err),
+            Err(err) => fail(format!("Failed to follow input file: {}", err)),
+            Ok(()) => {}
+        }
+    } else if merge {
+        if let Err(err) = merge_inputs(&input_paths, &settings, &record_parser, &mut pipeline) {
+            fail(err);
+        }
+    } else {
+        let label_sources = input_paths.len() > 1;
+        // All the inputs are opened first, so a missing one fails the run before any output
+        let readers = input_paths
+            .iter()
+            .map(|input_path| {
+                let reader = match (&settings.timestamp_parser, time_range.and_then(|time_range| time_range.since)) {
+                    (Some(_), Some(since)) => open_input_since(input_path, &record_parser, since),
+                    _ => open_input(input_path),
+                };
+                reader.unwrap_or_else(|err| fail(format!("Failed to open input {}: {}", input_path, err)))
+            })
+            .collect::<Vec<_>>();
+        for (input_path, reader) in input_paths.iter().zip(readers) {
+            if label_sources {
+                pipeline.set_source_label(Some(input_path.clone())).unwrap_or_else(exit_on_output_error);
+            }
+            for line in reader.lines() {
+                let line = line.unwrap_or_else(|err| fail(format!("Failed to read input {}: {}", input_path, err)));
+                pipeline.process_line(line.as_str()).unwrap_or_else(exit_on_output_error);
             }
         }
     }
 
-    println!("");
-    for processor in &processors {
-        if let Some(result) = processor.result() {
-            println!("{}", result);
+    pipeline.finish().unwrap_or_else(exit_on_output_error);
+    pipeline.print_results().unwrap_or_else(exit_on_output_error);
+}
+
+/// Reports an error and exits with a nonzero status, so scripts can tell that the run failed
+fn fail<T: Display>(message: T) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Reports an error of writing the output. A closed output, e.g. when piped to `head`, ends the program quietly.
+fn exit_on_output_error(err: io::Error) {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        process::exit(0);
+    }
+    eprintln!("Failed to write output: {}", err);
+    process::exit(1);
+}
+
+fn merge_inputs(
+    input_paths: &[String],
+    settings: &Settings,
+    record_parser: &RecordParser,
+    pipeline: &mut Pipeline,
+) -> Result<(), Box<dyn Error>> {
+    let source_labels = input_paths
+        .iter()
+        .zip(SOURCE_COLORS.iter().cycle())
+        .map(|(path, color)| Color::Fixed(*color).paint(path.as_str()).to_string())
+        .collect::<Vec<_>>();
+    let label_sources = input_paths.len() > 1;
+    let process_line = |source: usize, line: &str| {
+        if label_sources {
+            pipeline.set_source_label(Some(source_labels[source].clone())).unwrap_or_else(exit_on_output_error);
+        }
+        pipeline.process_line(line).unwrap_or_else(exit_on_output_error);
+    };
+
+    // Parsed timestamps are preferred, otherwise the text matched by the prefix is compared, which gives
+    // chronological order for timestamps like ISO 8601
+    match (&settings.timestamp_parser, &settings.prefix) {
+        (Some(_), _) => {
+            let record_parser = record_parser.clone();
+            merge::merge_inputs(input_paths, move |line: &str| record_parser.parse_timestamp(line), process_line)
+        }
+        (None, Some(prefix)) => {
+            let prefix_regex =
+                Regex::new(&format!("^(?:{})", prefix)).map_err(|err| format!("Invalid prefix: {}", err))?;
+            let parse_timestamp =
+                move |line: &str| prefix_regex.find(line).map(|timestamp| timestamp.as_str().to_owned());
+            merge::merge_inputs(input_paths, parse_timestamp, process_line)
+        }
+        (None, None) => {
+            return Err("Merging requires a timestamp format or a prefix that matches the timestamps".into());
+        }
+    }
+    .map_err(|err| format!("Failed to read input: {}", err))?;
+
+    Ok(())
+}
+
+fn parse_time_range(matches: &ArgMatches) -> Result<Option<TimeRange>, Box<dyn Error>> {
+    let parse_bound = |name: &str| {
+        matches
+            .value_of(name)
+            .map(|value| parse_time_bound(value).ok_or_else(|| format!("Invalid time for --{}: {}", name, value)))
+            .transpose()
+    };
+
+    match (parse_bound("since")?, parse_bound("until")?) {
+        (None, None) => Ok(None),
+        (since, until) => Ok(Some(TimeRange { since, until })),
+    }
+}
+
+fn parse_settings(matches: &ArgMatches) -> Result<Settings, Box<dyn Error>> {
+    let mut settings = parse_processor_settings(matches)?;
+    if let Some(format) = matches.value_of("timestamp_format") {
+        let format = format.parse::<TimestampFormat>()?;
+        settings.timestamp_parser = Some(match settings.timestamp_parser.take() {
+            Some(timestamp_parser) => {
+                TimestampParser::new(format, timestamp_parser.regex).with_field(timestamp_parser.field)
+            }
+            None => TimestampParser::new(format, None),
+        });
+    }
+
+    if let Some(input_format) = matches.value_of("input_format") {
+        settings.input_format = input_format.parse()?;
+    }
+    if let Some(template) = matches.value_of("template") {
+        settings.template = Some(template.parse()?);
+    }
+    if let Some(container_format) = matches.value_of("container") {
+        settings.container_format = Some(container_format.parse()?);
+    }
+
+    let continuation_pattern = matches
+        .value_of("continuation")
+        .map(|pattern| Grok::default().expand(pattern))
+        .transpose()?;
+    let continuation_pattern = continuation_pattern.as_deref();
+    if matches.is_present("multiline") || continuation_pattern.is_some() {
+        let start_pattern = settings.prefix.as_deref().filter(|_| matches.is_present("multiline"));
+        if start_pattern.is_none() && continuation_pattern.is_none() {
+            return Err("Joining multi-line records requires a prefix or a continuation pattern".into());
         }
+        settings.record_assembler = Some(RecordAssembler::from_patterns(start_pattern, continuation_pattern)?);
+    }
+
+    Ok(settings)
+}
+
+fn detect_config(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let detected = detect_sample(matches)?;
+    println!("{}", detected.to_json()?);
+    Ok(())
+}
+
+fn init_config(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = matches.value_of("file").unwrap();
+    let yes = matches.is_present("yes");
+    if path == "-" && !yes {
+        return Err("The answers are read from the standard input, pass --yes to read the log file from it".into());
+    }
+
+    let detected = detect_sample(matches)?;
+    // The config is printed to the standard output, so the questions are asked on the standard error
+    let config = if yes {
+        init::init(detected, &mut io::empty(), &mut io::sink())?
+    } else {
+        init::init(detected, &mut io::stdin().lock(), &mut io::stderr())?
+    };
+    println!("{}", config.to_json()?);
+    Ok(())
+}
+
+/// Detects the format of the first lines of the file
+fn detect_sample(matches: &ArgMatches) -> Result<detect::DetectedConfig, Box<dyn Error>> {
+    let path = matches.value_of("file").unwrap();
+    let line_count = matches.value_of("lines").unwrap().parse::<usize>()?;
+    let lines = open_input(path)
+        .map_err(|err| format!("Failed to open input file {}: {}", path, err))?
+        .lines()
+        .take(line_count)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let detected = detect::detect(&lines);
+    if detected.input_format.is_none() && detected.prefix.is_none() {
+        return Err("Failed to detect the format of the log file".into());
     }
+    Ok(detected)
 }
 
-fn parse_processors(matches: ArgMatches) -> Result<Vec<Box<dyn Processor>>, Box<dyn std::error::Error>> {
+fn parse_processor_settings(matches: &ArgMatches) -> Result<Settings, Box<dyn Error>> {
+    let grok = Grok::default();
     match matches.subcommand() {
         ("use-config", Some(config_matches)) => {
             let config_path = PathBuf::from(config_matches.value_of("config_path").unwrap());
-            let config_file = File::open(config_path).map_err(|err| format!("Failed to open config file: {}", err))?;
-            let config = Config::from_json_file(config_file)?;
+            let config_format = match config_matches.value_of("config_format") {
+                Some(config_format) => config_format.parse()?,
+                None => ConfigFormat::from_path(&config_path),
+            };
+            let config = Config::from_path(&config_path, config_format)?;
 
             let mut processors = config.pattern_colors
                 .map(|pattern_colors| vec![Box::new(Colorize::new(pattern_colors)) as Box<dyn Processor>])
                 .unwrap_or_default();
 
+            let event_patterns = config.events.clone();
             processors.extend(
                 config
                     .events
@@ -111,10 +491,27 @@ fn parse_processors(matches: ArgMatches) -> Result<Vec<Box<dyn Processor>>, Box<
                     .map(|state| Box::new(state) as Box<dyn Processor>),
             );
 
-            Ok(processors)
+            processors.extend(
+                config
+                    .field_counts
+                    .into_iter()
+                    .map(|field_count| Box::new(field_count) as Box<dyn Processor>),
+            );
+
+            Ok(Settings {
+                processors,
+                event_patterns,
+                prefix: config.prefix,
+                timestamp_parser: config.timestamp,
+                record_assembler: config.multiline,
+                input_format: config.input_format,
+                template: config.template,
+                container_format: config.container,
+            })
         }
         ("colorize", Some(colorize_matches)) => {
-            let prefix = colorize_matches.value_of("prefix");
+            let prefix = colorize_matches.value_of("prefix").map(|prefix| grok.expand(prefix)).transpose()?;
+            let prefix = prefix.as_deref();
 
             let pattern_colors = colorize_matches
                 .values_of("patterns")
@@ -126,46 +523,89 @@ fn parse_processors(matches: ArgMatches) -> Result<Vec<Box<dyn Processor>>, Box<
                     let regex_value = params[0];
                     let color_value = params[1];
                     let color = Color::Fixed(color_value.parse::<u8>().map_err(|err| ParseColorError::new(color_value, err))?);
-                    let regex = create_regex_with_prefix(&prefix, regex_value)?;
-                    Ok(PatternColor { color, regex })
+                    let regex = create_regex_with_prefix(&prefix, &grok.expand(regex_value)?)?;
+                    Ok(PatternColor { color, regex, field: None })
                 })
-                .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
 
-            Ok(vec![Box::new(Colorize::new(pattern_colors)) as Box<dyn Processor>])
+            Ok(Settings {
+                processors: vec![Box::new(Colorize::new(pattern_colors)) as Box<dyn Processor>],
+                prefix: prefix.map(|prefix| prefix.to_owned()),
+                ..Default::default()
+            })
         }
         ("events", Some(events_matches)) => {
-            let prefix = events_matches.value_of("prefix");
+            let prefix = events_matches.value_of("prefix").map(|prefix| grok.expand(prefix)).transpose()?;
+            let prefix = prefix.as_deref();
             let color = events_matches
                 .value_of("color")
                 .map(|color| color.parse::<u8>().map_err(|err| ParseColorError::new(color, err)))
                 .transpose()?
-                .map(|color| Color::Fixed(color));
+                .map(Color::Fixed);
 
             let start_regex_value = events_matches.value_of("start").unwrap();
-            let start_regex = create_regex_with_prefix(&prefix, start_regex_value)?;
+            let start_regex = create_regex_with_prefix(&prefix, &grok.expand(start_regex_value)?)?;
 
             let end_regex_value = events_matches.value_of("end").unwrap();
-            let end_regex = create_regex_with_prefix(&prefix, end_regex_value)?;
+            let end_regex = create_regex_with_prefix(&prefix, &grok.expand(end_regex_value)?)?;
+
+            let max_lines = events_matches
+                .value_of("max_lines")
+                .map(|max_lines| {
+                    max_lines
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|max_lines| *max_lines >= 2)
+                        .ok_or_else(|| format!("Invalid maximum number of lines: {}", max_lines))
+                })
+                .transpose()?;
+            let event_patterns = EventPatterns::new(start_regex, end_regex)
+                .with_color(color)
+                .with_key(events_matches.value_of("key").map(|key| key.to_owned()))
+                .with_report_orphans(events_matches.is_present("orphans"))
+                .with_timeout(events_matches.value_of("timeout").map(str::parse).transpose()?)
+                .with_max_lines(max_lines);
+            if !event_patterns.has_key_captures() {
+                return Err("The start and end patterns must capture the key of events".into());
+            }
 
-            Ok(vec![Box::new(EventProcessor::new(EventPatterns {
-                start_regex,
-                end_regex,
-                color,
-            })) as Box<dyn Processor>])
+            Ok(Settings {
+                processors: vec![Box::new(EventProcessor::new(event_patterns.clone())) as Box<dyn Processor>],
+                event_patterns: vec![event_patterns],
+                prefix: prefix.map(|prefix| prefix.to_owned()),
+                ..Default::default()
+            })
         }
         ("states", Some(states_matches)) => {
-            let prefix = states_matches.value_of("prefix");
+            let prefix = states_matches.value_of("prefix").map(|prefix| grok.expand(prefix)).transpose()?;
+            let prefix = prefix.as_deref();
             let color = states_matches
                 .value_of("color")
                 .map(|color| color.parse::<u8>().map_err(|err| ParseColorError::new(color, err)))
                 .transpose()?
-                .map(|color| Color::Fixed(color));
+                .map(Color::Fixed);
 
             let regex_value = states_matches.value_of("regex").unwrap();
-            let regex = create_regex_with_prefix(&prefix, regex_value)?;
+            let regex = create_regex_with_prefix(&prefix, &grok.expand(regex_value)?)?;
 
-            Ok(vec![Box::new(StateProcessor::new(regex, color)) as Box<dyn Processor>])
+            Ok(Settings {
+                processors: vec![Box::new(StateProcessor::new(regex, color)) as Box<dyn Processor>],
+                prefix: prefix.map(|prefix| prefix.to_owned()),
+                ..Default::default()
+            })
         }
+        ("count", Some(count_matches)) => Ok(Settings {
+            processors: count_matches
+                .values_of("fields")
+                .unwrap()
+                .map(|field| Box::new(FieldCounter::new(field.to_owned())) as Box<dyn Processor>)
+                .collect(),
+            prefix: count_matches
+                .value_of("prefix")
+                .map(|prefix| grok.expand(prefix))
+                .transpose()?,
+            ..Default::default()
+        }),
         _ => unreachable!(),
     }
 }
\ No newline at end of file
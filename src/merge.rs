@@ -0,0 +1,149 @@
+use crate::input::open_input;
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io::{self, BufRead},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+/// Number of chunks buffered for every input while merging
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A line with a timestamp followed by its continuation lines, which don't have a timestamp of their own
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Chunk<T> {
+    timestamp: Option<T>,
+    source: usize,
+    lines: Vec<String>,
+}
+
+/// Reads the inputs concurrently and calls `process_line` with the index of the source and the line in chronological
+/// order. The timestamp of a line is returned by `parse_timestamp`. Lines without a timestamp stay attached to the
+/// preceding line.
+pub fn merge_inputs<T, P, F>(paths: &[String], parse_timestamp: P, mut process_line: F) -> io::Result<()>
+where
+    T: Ord + Send + 'static,
+    P: Fn(&str) -> Option<T> + Clone + Send + 'static,
+    F: FnMut(usize, &str),
+{
+    let receivers = paths
+        .iter()
+        .enumerate()
+        .map(|(source, path)| spawn_reader(source, path.clone(), parse_timestamp.clone()))
+        .collect::<Vec<_>>();
+
+    let mut heads = BinaryHeap::new();
+    for receiver in &receivers {
+        if let Some(chunk) = receiver.recv().ok().transpose()? {
+            heads.push(Reverse(chunk));
+        }
+    }
+
+    while let Some(Reverse(chunk)) = heads.pop() {
+        for line in &chunk.lines {
+            process_line(chunk.source, line);
+        }
+
+        if let Some(next_chunk) = receivers[chunk.source].recv().ok().transpose()? {
+            heads.push(Reverse(next_chunk));
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_reader<T, P>(source: usize, path: String, parse_timestamp: P) -> Receiver<io::Result<Chunk<T>>>
+where
+    T: Send + 'static,
+    P: Fn(&str) -> Option<T> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+    thread::spawn(move || {
+        if let Err(err) = read_chunks(source, &path, parse_timestamp, &sender) {
+            let _ = sender.send(Err(io::Error::new(err.kind(), format!("{}: {}", path, err))));
+        }
+    });
+    receiver
+}
+
+fn read_chunks<T, P>(
+    source: usize,
+    path: &str,
+    parse_timestamp: P,
+    sender: &SyncSender<io::Result<Chunk<T>>>,
+) -> io::Result<()>
+where
+    P: Fn(&str) -> Option<T>,
+{
+    let mut current_chunk: Option<Chunk<T>> = None;
+    for line in open_input(path)?.lines() {
+        let line = line?;
+        let timestamp = parse_timestamp(&line);
+        if let (Some(chunk), None) = (current_chunk.as_mut(), &timestamp) {
+            chunk.lines.push(line);
+            continue;
+        }
+
+        if let Some(chunk) = current_chunk.take() {
+            if sender.send(Ok(chunk)).is_err() {
+                // The merging has stopped
+                return Ok(());
+            }
+        }
+        current_chunk = Some(Chunk {
+            timestamp,
+            source,
+            lines: vec![line],
+        });
+    }
+
+    if let Some(chunk) = current_chunk {
+        let _ = sender.send(Ok(chunk));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_inputs;
+    use regex::Regex;
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_merge_inputs() {
+        let first_path = env::temp_dir().join(format!("logan_merge_test_first_{}.log", process::id()));
+        let second_path = env::temp_dir().join(format!("logan_merge_test_second_{}.log", process::id()));
+        fs::write(
+            &first_path,
+            "2020-01-01 10:00:00 INFO First\n2020-01-01 10:00:03 ERROR Failed\nAn unknown error occurred\n",
+        )
+        .unwrap();
+        fs::write(&second_path, "2020-01-01 10:00:01 INFO Second\n2020-01-01 10:00:04 INFO Last\n").unwrap();
+
+        let paths = vec![
+            first_path.to_string_lossy().into_owned(),
+            second_path.to_string_lossy().into_owned(),
+        ];
+        let timestamp_regex = Regex::new(r"^[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2}").unwrap();
+        let parse_timestamp =
+            move |line: &str| timestamp_regex.find(line).map(|timestamp| timestamp.as_str().to_owned());
+        let mut lines = Vec::new();
+        merge_inputs(&paths, parse_timestamp, |source, line| lines.push((source, line.to_owned()))).unwrap();
+
+        assert_eq!(
+            lines,
+            vec![
+                (0, "2020-01-01 10:00:00 INFO First".to_owned()),
+                (1, "2020-01-01 10:00:01 INFO Second".to_owned()),
+                (0, "2020-01-01 10:00:03 ERROR Failed".to_owned()),
+                (0, "An unknown error occurred".to_owned()),
+                (1, "2020-01-01 10:00:04 INFO Last".to_owned()),
+            ]
+        );
+
+        fs::remove_file(&first_path).unwrap();
+        fs::remove_file(&second_path).unwrap();
+    }
+}
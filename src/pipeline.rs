@@ -0,0 +1,214 @@
+use crate::{
+    formats::ContainerDecoder,
+    processors::Processor,
+    record::{RecordAssembler, RecordParser},
+    timestamp::TimeFilter,
+};
+use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+/// Feeds lines through a chain of processors and prints their output
+pub struct Pipeline {
+    processors: Vec<Box<dyn Processor>>,
+    container_decoder: Option<ContainerDecoder>,
+    record_assembler: Option<RecordAssembler>,
+    /// The fields of the first line of the record that is being assembled
+    record_fields: Option<HashMap<String, String>>,
+    record_parser: RecordParser,
+    source_label: Option<String>,
+    time_filter: Option<TimeFilter>,
+    output: io::StdoutLock<'static>,
+    has_output: bool,
+    last_process_required_separator: bool,
+}
+
+impl Pipeline {
+    pub fn new(processors: Vec<Box<dyn Processor>>) -> Self {
+        Self {
+            processors,
+            container_decoder: None,
+            record_assembler: None,
+            record_fields: None,
+            record_parser: RecordParser::default(),
+            source_label: None,
+            time_filter: None,
+            output: io::stdout().lock(),
+            has_output: false,
+            last_process_required_separator: false,
+        }
+    }
+
+    /// Sets the decoder that unwraps the lines of container log files before they are assembled into records
+    pub fn set_container_decoder(&mut self, container_decoder: Option<ContainerDecoder>) {
+        self.container_decoder = container_decoder;
+    }
+
+    /// Sets the assembler that joins continuation lines into multi-line records before they reach the processors
+    pub fn set_record_assembler(&mut self, record_assembler: Option<RecordAssembler>) {
+        self.record_assembler = record_assembler;
+    }
+
+    /// Sets the parser that extracts the fields and the timestamp of the records
+    pub fn set_record_parser(&mut self, record_parser: RecordParser) {
+        self.record_parser = record_parser;
+    }
+
+    /// Sets the label that is prepended to every output line. Used to tell apart the sources of the lines when
+    /// multiple inputs are processed. The label may be painted. A record never spans sources, so the pending record
+    /// is processed when the source changes.
+    pub fn set_source_label(&mut self, source_label: Option<String>) -> io::Result<()> {
+        if self.source_label != source_label {
+            self.flush()?;
+            self.source_label = source_label;
+        }
+        Ok(())
+    }
+
+    /// Sets the filter that drops the lines outside of a time range before they reach the processors
+    pub fn set_time_filter(&mut self, time_filter: Option<TimeFilter>) {
+        self.time_filter = time_filter;
+    }
+
+    pub fn process_line(&mut self, line: &str) -> io::Result<()> {
+        match &mut self.container_decoder {
+            Some(container_decoder) => match container_decoder.push_line(line) {
+                Some(container_line) => self.assemble_line(&container_line.text, container_line.fields),
+                None => Ok(()),
+            },
+            None => self.assemble_line(line, HashMap::new()),
+        }
+    }
+
+    fn assemble_line(&mut self, line: &str, fields: HashMap<String, String>) -> io::Result<()> {
+        match &mut self.record_assembler {
+            Some(record_assembler) => {
+                if let Some(record) = record_assembler.push_line(line) {
+                    let record_fields = self.record_fields.replace(fields).unwrap_or_default();
+                    return self.process_record(record, record_fields);
+                } else if self.record_fields.is_none() {
+                    self.record_fields = Some(fields);
+                }
+                Ok(())
+            }
+            None => self.process_record(line.to_owned(), fields),
+        }
+    }
+
+    /// Processes the record that is being assembled. Must be called at the end of the input.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let container_lines = self.container_decoder.as_mut().map(|decoder| decoder.flush()).unwrap_or_default();
+        for container_line in container_lines {
+            self.assemble_line(&container_line.text, container_line.fields)?;
+        }
+        if let Some(record) = self.record_assembler.as_mut().and_then(|record_assembler| record_assembler.flush()) {
+            let record_fields = self.record_fields.take().unwrap_or_default();
+            self.process_record(record, record_fields)?;
+        }
+        self.output.flush()
+    }
+
+    fn process_record(&mut self, text: String, fields: HashMap<String, String>) -> io::Result<()> {
+        let record = self.record_parser.parse_with_fields(text, fields);
+        if let Some(time_filter) = &mut self.time_filter {
+            if !time_filter.accepts(record.timestamp) {
+                return Ok(());
+            }
+        }
+
+        for processor in &mut self.processors {
+            if let Some(output) = processor.process_record(&record) {
+                let output = match &self.source_label {
+                    Some(label) => label_lines(label, &output),
+                    None => output,
+                };
+                if self.has_output && (self.last_process_required_separator || processor.requires_separator()) {
+                    writeln!(self.output, "{sep}\n{}", output, sep = "-".repeat(50))?;
+                } else {
+                    writeln!(self.output, "{}", output)?;
+                }
+
+                self.has_output = true;
+                self.last_process_required_separator = processor.requires_separator();
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the pending lines and reports what the processors left unfinished at the end of the input. The
+    /// reports aren't labeled, since they can refer to lines of several sources.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        for processor in &mut self.processors {
+            if let Some(output) = processor.finish() {
+                if self.has_output {
+                    writeln!(self.output, "{sep}\n{}", output, sep = "-".repeat(50))?;
+                } else {
+                    writeln!(self.output, "{}", output)?;
+                }
+
+                self.has_output = true;
+                self.last_process_required_separator = processor.requires_separator();
+            }
+        }
+        self.output.flush()
+    }
+
+    pub fn print_results(&mut self) -> io::Result<()> {
+        writeln!(self.output)?;
+        for processor in &self.processors {
+            if let Some(result) = processor.result() {
+                writeln!(self.output, "{}", result)?;
+            }
+        }
+        self.output.flush()
+    }
+}
+
+/// Prepends the label to every line of the output. If the output is painted, the style is applied again after the
+/// label on every line, since a painted label resets it.
+fn label_lines(label: &str, output: &str) -> String {
+    let style_prefix = if output.starts_with("\x1b[") {
+        output.find('m').map(|end| &output[..=end]).unwrap_or_default()
+    } else {
+        ""
+    };
+
+    output
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            if index == 0 {
+                format!("{}: {}", label, line)
+            } else {
+                format!("{}: {}{}", label, style_prefix, line)
+            }
+        })
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::label_lines;
+    use ansi_term::Color;
+
+    #[test]
+    fn test_label_lines() {
+        assert_eq!(label_lines("app.log", "first\nsecond"), "app.log: first\napp.log: second");
+
+        let label = Color::Fixed(33).paint("app.log").to_string();
+        let output = Color::Fixed(28).paint("first\nsecond").to_string();
+        assert_eq!(
+            label_lines(&label, &output),
+            format!(
+                "{label}: {}\n{label}: {}{}",
+                output.lines().next().unwrap(),
+                Color::Fixed(28).prefix(),
+                output.lines().nth(1).unwrap(),
+                label = label
+            )
+        );
+    }
+}
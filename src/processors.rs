@@ -1,22 +1,47 @@
+use crate::{record::Record, timestamp::Timestamp};
 use ansi_term::Color;
+use chrono::Duration;
+use itertools::Itertools;
 use regex::Regex;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 pub trait Processor {
-    fn process_line(&mut self, line: &str) -> Option<String>;
+    fn process_record(&mut self, record: &Record) -> Option<String>;
+
+    #[cfg(test)]
+    fn process_line(&mut self, line: &str) -> Option<String> {
+        self.process_record(&Record::new(line))
+    }
 
     fn requires_separator(&self) -> bool {
         false
     }
 
+    /// Called once at the end of the input. Returns the output about what was left unfinished, e.g. events that
+    /// never ended.
+    fn finish(&mut self) -> Option<String> {
+        None
+    }
+
     fn result(&self) -> Option<String> {
         None
     }
 }
 
+/// Matches the regex against the given field of the record or its whole text if no field is given
+fn is_match(regex: &Regex, field: Option<&str>, record: &Record) -> bool {
+    record.target(field).is_some_and(|target| regex.is_match(target))
+}
+
 #[derive(Debug, Clone)]
 pub struct PatternColor {
     pub regex: Regex,
     pub color: Color,
+    pub field: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,12 +60,17 @@ impl Colorize {
 }
 
 impl Processor for Colorize {
-    fn process_line(&mut self, line: &str) -> Option<String> {
-        if let Some(color) = self.pattern_colors.iter().find(|pattern| pattern.regex.is_match(line)).map(|pattern| pattern.color) {
+    fn process_record(&mut self, record: &Record) -> Option<String> {
+        if let Some(color) = self
+            .pattern_colors
+            .iter()
+            .find(|pattern| is_match(&pattern.regex, pattern.field.as_deref(), record))
+            .map(|pattern| pattern.color)
+        {
             self.current_color = Some(color);
         }
 
-        Some(self.current_color.unwrap_or(Color::White).paint(line).to_string())
+        Some(self.current_color.unwrap_or(Color::White).paint(&record.text).to_string())
     }
 }
 
@@ -49,70 +79,547 @@ pub struct EventPatterns {
     pub start_regex: Regex,
     pub end_regex: Regex,
     pub color: Option<Color>,
+    pub field: Option<String>,
+    /// The name of a capture group of both patterns that correlates the end of an event with its start, e.g.
+    /// `request_id`. Events with different keys can overlap.
+    pub key: Option<String>,
+    /// Lists the end lines without a start at the end of the input
+    pub report_orphans: bool,
+    /// Flushes events that stay open for longer as timed out
+    pub timeout: Option<EventTimeout>,
+    /// Flushes events as truncated once they have this many lines
+    pub max_lines: Option<usize>,
+    /// The events that are only recognized inside an event of these patterns
+    pub children: Vec<EventPatterns>,
+}
+
+impl EventPatterns {
+    pub fn new(start_regex: Regex, end_regex: Regex) -> Self {
+        Self {
+            start_regex,
+            end_regex,
+            color: None,
+            field: None,
+            key: None,
+            report_orphans: false,
+            timeout: None,
+            max_lines: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_color(mut self, color: Option<Color>) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Matches the patterns against the given field instead of the whole record
+    pub fn with_field(mut self, field: Option<String>) -> Self {
+        self.field = field;
+        self
+    }
+
+    pub fn with_key(mut self, key: Option<String>) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn with_report_orphans(mut self, report_orphans: bool) -> Self {
+        self.report_orphans = report_orphans;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<EventTimeout>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_max_lines(mut self, max_lines: Option<usize>) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<EventPatterns>) -> Self {
+        self.children = children;
+        self
+    }
+
+    /// Checks that both patterns capture the key
+    pub fn has_key_captures(&self) -> bool {
+        self.key.as_deref().is_none_or(|key| {
+            [&self.start_regex, &self.end_regex]
+                .iter()
+                .all(|regex| regex.capture_names().flatten().any(|name| name == key))
+        })
+    }
+
+    /// Returns the key of the event if the regex matches the record. Events without a key name have an empty key.
+    fn match_key(&self, regex: &Regex, record: &Record) -> Option<String> {
+        let captures = regex.captures(record.target(self.field.as_deref())?)?;
+        Some(
+            self.key
+                .as_deref()
+                .and_then(|key| captures.name(key))
+                .map(|key| key.as_str().to_owned())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// How long an event can stay open before it's flushed as timed out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventTimeout {
+    /// The time in the log between the start line and the current line, e.g. `30s` or `1m 30s`. Only events whose
+    /// start line has a timestamp time out.
+    Duration(Duration),
+    /// The number of lines after the start line, e.g. `100`
+    Lines(usize),
+}
+
+impl FromStr for EventTimeout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(lines) = value.trim().parse() {
+            return Ok(EventTimeout::Lines(lines));
+        }
+        parse_duration(value).map(EventTimeout::Duration).ok_or_else(|| {
+            format!(
+                "Invalid timeout: {}, expected a number of lines or a duration, e.g. 500ms, 30s or 1h 5m",
+                value
+            )
+        })
+    }
+}
+
+impl Display for EventTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventTimeout::Duration(duration) => f.write_str(&format_duration(*duration)),
+            EventTimeout::Lines(lines) => write!(f, "{} lines", lines),
+        }
+    }
+}
+
+/// Why an open event is flushed before its end line
+#[derive(Debug, Clone, Copy)]
+enum FlushReason {
+    Timeout(EventTimeout),
+    MaxLines(usize),
+}
+
+/// The percentiles of the event durations in the result
+const DURATION_PERCENTILES: [usize; 3] = [50, 90, 99];
+
+/// An event that started but hasn't ended yet
+#[derive(Debug, Clone)]
+struct OpenEvent {
+    /// The number of the start line among the processed lines
+    start_line: usize,
+    /// The formatted lines, including the child events
+    lines: String,
+    line_count: usize,
+    start: Option<Timestamp>,
+    /// The processors of the child events inside this event
+    children: Vec<EventProcessor>,
 }
 
 #[derive(Debug, Clone)]
 pub struct EventProcessor {
     event_patterns: EventPatterns,
-    current_event: Option<String>,
+    /// Nested events, i.e. events with children or inside another event, are printed as a tree of indented lines
+    nested: bool,
+    /// The events that started but haven't ended yet, by their keys
+    open_events: HashMap<String, OpenEvent>,
+    /// The number of processed lines
+    line_count: usize,
+    /// Tells whether the last processed line belonged to an event
+    took_line: bool,
+    /// The end lines that didn't end an open event, kept if they are reported
+    orphan_lines: Vec<String>,
+    /// The durations of the completed events whose start and end lines have timestamps
+    durations: Vec<Duration>,
+    /// Collect the durations of the child events of all the events
+    children: Vec<EventProcessor>,
 }
 
 impl EventProcessor {
     pub fn new(event_patterns: EventPatterns) -> Self {
+        let nested = !event_patterns.children.is_empty();
+        Self::with_nesting(event_patterns, nested)
+    }
+
+    fn with_nesting(event_patterns: EventPatterns, nested: bool) -> Self {
+        let children = event_patterns
+            .children
+            .iter()
+            .map(|child_patterns| Self::with_nesting(child_patterns.clone(), true))
+            .collect();
         Self {
             event_patterns,
-            current_event: None,
+            nested,
+            open_events: HashMap::new(),
+            line_count: 0,
+            took_line: false,
+            orphan_lines: Vec::new(),
+            durations: Vec::new(),
+            children,
         }
     }
-}
 
-impl Processor for EventProcessor {
-    fn process_line(&mut self, line: &str) -> Option<String> {
-        match &mut self.current_event {
-            Some(_) if self.event_patterns.end_regex.is_match(line) => {
-                let event = format!("Event:\n{}{}", self.current_event.take().unwrap(), line);
-                Some(
-                    self.event_patterns
-                        .color
-                        .map(|color| color.paint(&event).to_string())
-                        .unwrap_or(event),
-                )
+    /// Returns e.g. `Event` or `Event request_id=a1` for keyed events
+    fn event_title(&self, kind: &str, key: &str) -> String {
+        match &self.event_patterns.key {
+            Some(key_name) => format!("{} {}={}", kind, key_name, key),
+            None => kind.to_owned(),
+        }
+    }
+
+    fn paint(&self, text: String) -> String {
+        self.event_patterns
+            .color
+            .map(|color| color.paint(&text).to_string())
+            .unwrap_or(text)
+    }
+
+    /// Formats a line of an event. The lines of nested events are indented and painted one by one, so that the child
+    /// events keep their own colors.
+    fn format_line(&self, line: &str) -> String {
+        if self.nested {
+            format!("  {}\n", self.paint(line.to_owned()))
+        } else {
+            format!("{}\n", line)
+        }
+    }
+
+    fn format_event(&self, title: &str, lines: &str, end_line: Option<&str>) -> String {
+        let end_line = end_line.map(|line| self.format_line(line)).unwrap_or_default();
+        if self.nested {
+            format!("{}\n{}{}", self.paint(format!("{}:", title)), lines, end_line)
+                .trim_end_matches('\n')
+                .to_owned()
+        } else {
+            self.paint(format!("{}:\n{}{}", title, lines, end_line).trim_end_matches('\n').to_owned())
+        }
+    }
+
+    /// Ends the child events that are still open as incomplete and keeps the durations of the child events
+    fn close_children(&mut self, event: &mut OpenEvent) {
+        for (mut child, child_durations) in event.children.drain(..).zip(&mut self.children) {
+            if let Some(output) = child.finish() {
+                event.lines += &indent_lines(&output);
             }
-            Some(event) => {
-                *event += format!("{}\n", line).as_str();
-                None
+            child_durations.absorb(child);
+        }
+    }
+
+    /// Adds the durations of the events of another processor with the same patterns
+    fn absorb(&mut self, other: EventProcessor) {
+        self.durations.extend(other.durations);
+        for (child, other_child) in self.children.iter_mut().zip(other.children) {
+            child.absorb(other_child);
+        }
+    }
+
+    /// Flushes the open events that have been open for longer than the timeout before the record
+    fn time_out_events(&mut self, record: &Record) -> Vec<String> {
+        let timeout = match self.event_patterns.timeout {
+            Some(timeout) => timeout,
+            None => return Vec::new(),
+        };
+        let keys = self
+            .open_events
+            .iter()
+            .filter(|(_, event)| match timeout {
+                EventTimeout::Duration(duration) => match (event.start, record.timestamp) {
+                    (Some(start), Some(timestamp)) => timestamp - start > duration,
+                    _ => false,
+                },
+                EventTimeout::Lines(lines) => self.line_count - event.start_line > lines,
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        self.flush_events(keys, FlushReason::Timeout(timeout))
+    }
+
+    /// Flushes the open events that reached the maximum number of lines
+    fn cap_events(&mut self) -> Vec<String> {
+        let max_lines = match self.event_patterns.max_lines {
+            Some(max_lines) => max_lines,
+            None => return Vec::new(),
+        };
+        let keys = self
+            .open_events
+            .iter()
+            .filter(|(_, event)| event.line_count >= max_lines)
+            .map(|(key, _)| key.clone())
+            .collect();
+        self.flush_events(keys, FlushReason::MaxLines(max_lines))
+    }
+
+    fn flush_events(&mut self, keys: Vec<String>, reason: FlushReason) -> Vec<String> {
+        let events = keys
+            .into_iter()
+            .filter_map(|key| self.open_events.remove(&key).map(|event| (key, event)))
+            .sorted_by_key(|(_, event)| event.start_line)
+            .collect::<Vec<_>>();
+        events
+            .into_iter()
+            .map(|(key, mut event)| {
+                self.close_children(&mut event);
+                let title = match reason {
+                    FlushReason::Timeout(timeout) => {
+                        format!("{} (after {})", self.event_title("Timed out event", &key), timeout)
+                    }
+                    FlushReason::MaxLines(max_lines) => {
+                        format!("{} (max_lines {})", self.event_title("Truncated event", &key), max_lines)
+                    }
+                };
+                self.format_event(&title, &event.lines, None)
+            })
+            .collect()
+    }
+
+    fn process_event_line(&mut self, record: &Record) -> Option<String> {
+        self.took_line = true;
+        let line = record.text.as_str();
+        let end_key = self.event_patterns.match_key(&self.event_patterns.end_regex, record);
+        if let Some(mut event) = end_key.as_ref().and_then(|key| self.open_events.remove(key)) {
+            self.close_children(&mut event);
+            let mut title = self.event_title("Event", &end_key.unwrap());
+            if let (Some(start), Some(end)) = (event.start, record.timestamp) {
+                let duration = end - start;
+                title += &format!(" ({})", format_duration(duration));
+                self.durations.push(duration);
             }
-            None => {
-                if self.event_patterns.start_regex.is_match(line) {
-                    self.current_event = Some(format!("{}\n", line));
-                }
+            return Some(self.format_event(&title, &event.lines, Some(line)));
+        }
 
-                None
+        let start_key = self.event_patterns.match_key(&self.event_patterns.start_regex, record);
+        if let Some(key) = &start_key {
+            if !self.open_events.contains_key(key) {
+                let event = OpenEvent {
+                    start_line: self.line_count,
+                    lines: self.format_line(line),
+                    line_count: 1,
+                    start: record.timestamp,
+                    children: self.children.iter().map(|child| child.new_event_processor()).collect(),
+                };
+                self.open_events.insert(key.clone(), event);
+                return None;
             }
+        } else if end_key.is_some() && self.event_patterns.report_orphans {
+            self.orphan_lines.push(line.to_owned());
+        }
+
+        // Lines between the start and the end belong to the open event with the same key, captured by the patterns or
+        // found in the fields. Lines without a key belong to the most recently started event, since overlapping events
+        // would otherwise show each other's lines. Lines that belong to a child event are part of the child event only.
+        let line_key = self
+            .event_patterns
+            .key
+            .as_deref()
+            .and_then(|key_name| {
+                end_key
+                    .or(start_key)
+                    .or_else(|| record.field(key_name).map(|key| key.to_owned()))
+            })
+            .or_else(|| {
+                self.open_events
+                    .iter()
+                    .max_by_key(|(_, event)| event.start_line)
+                    .map(|(key, _)| key.clone())
+            });
+        let formatted_line = self.format_line(line);
+        let event = match line_key.and_then(|key| self.open_events.get_mut(&key)) {
+            Some(event) => event,
+            None => {
+                self.took_line = false;
+                return None;
+            }
+        };
+        event.line_count += 1;
+        let mut child_took_line = false;
+        for child in &mut event.children {
+            if let Some(output) = child.process_record(record) {
+                event.lines += &indent_lines(&output);
+            }
+            child_took_line |= child.took_line;
+        }
+        if !child_took_line {
+            event.lines += &formatted_line;
+        }
+        None
+    }
+
+    fn duration_statistics(&self) -> String {
+        let durations = self.durations.iter().sorted().collect::<Vec<_>>();
+        let total = durations.iter().fold(Duration::zero(), |total, duration| total + **duration);
+        let mut statistics = vec![
+            ("count".to_owned(), durations.len().to_string()),
+            ("min".to_owned(), format_duration(*durations[0])),
+            ("max".to_owned(), format_duration(*durations[durations.len() - 1])),
+            ("mean".to_owned(), format_duration(total / durations.len() as i32)),
+        ];
+        // Nearest-rank percentiles
+        statistics.extend(DURATION_PERCENTILES.iter().map(|percentile| {
+            let rank = (percentile * durations.len()).div_ceil(100).max(1);
+            (format!("p{}", percentile), format_duration(*durations[rank - 1]))
+        }));
+
+        format!(
+            "Durations of events from \"{}\" to \"{}\":\n{}",
+            self.event_patterns.start_regex,
+            self.event_patterns.end_regex,
+            statistics
+                .iter()
+                .map(|(name, value)| format!("{:>8} {}", name, value))
+                .join("\n")
+        )
+    }
+
+    /// Returns a processor with the same patterns for the child events inside a new event
+    fn new_event_processor(&self) -> Self {
+        Self::with_nesting(self.event_patterns.clone(), self.nested)
+    }
+}
+
+/// Indents the lines of a child event inside its parent event
+fn indent_lines(output: &str) -> String {
+    output.lines().map(|line| format!("  {}\n", line)).collect()
+}
+
+impl Processor for EventProcessor {
+    /// Events that time out are flushed before the record is processed, so the record can start a new event
+    fn process_record(&mut self, record: &Record) -> Option<String> {
+        let mut outputs = self.time_out_events(record);
+        outputs.extend(self.process_event_line(record));
+        outputs.extend(self.cap_events());
+        self.line_count += 1;
+
+        if outputs.is_empty() {
+            None
+        } else {
+            Some(outputs.join("\n"))
         }
     }
 
     fn requires_separator(&self) -> bool {
         true
     }
+
+    fn finish(&mut self) -> Option<String> {
+        let events = self
+            .open_events
+            .drain()
+            .sorted_by_key(|(_, event)| event.start_line)
+            .collect::<Vec<_>>();
+        let mut reports = events
+            .into_iter()
+            .map(|(key, mut event)| {
+                self.close_children(&mut event);
+                self.format_event(&self.event_title("Incomplete event", &key), &event.lines, None)
+            })
+            .collect::<Vec<_>>();
+        if !self.orphan_lines.is_empty() {
+            let orphan_lines = format!("End lines without a start:\n{}", self.orphan_lines.drain(..).join("\n"));
+            reports.push(self.paint(orphan_lines));
+        }
+
+        if reports.is_empty() {
+            None
+        } else {
+            Some(reports.join("\n"))
+        }
+    }
+
+    /// The durations of the child events follow the durations of their parent events
+    fn result(&self) -> Option<String> {
+        let mut results = Vec::new();
+        if !self.durations.is_empty() {
+            results.push(self.paint(self.duration_statistics()));
+        }
+        results.extend(self.children.iter().filter_map(|child| child.result()));
+
+        if results.is_empty() {
+            None
+        } else {
+            Some(results.join("\n"))
+        }
+    }
+}
+
+/// Parses a duration of one or more numbers with a unit, e.g. `850ms`, `1.5s` or `1h 2m 5.250s`, the format of
+/// `format_duration`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let part_regex = Regex::new(r"^\s*(\d+(?:\.\d+)?)\s*(ms|h|m|s)").unwrap();
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    let mut milliseconds = 0.0;
+    while let Some(captures) = part_regex.captures(rest) {
+        let number = captures[1].parse::<f64>().ok()?;
+        milliseconds += number
+            * match &captures[2] {
+                "ms" => 1.0,
+                "s" => 1000.0,
+                "m" => 60_000.0,
+                _ => 3_600_000.0,
+            };
+        rest = &rest[captures[0].len()..];
+    }
+    if rest.is_empty() {
+        Some(Duration::milliseconds(milliseconds.round() as i64))
+    } else {
+        None
+    }
+}
+
+/// Formats a duration with millisecond precision, e.g. `850ms`, `2.500s` or `1h 2m 5.250s`
+pub fn format_duration(duration: Duration) -> String {
+    let milliseconds = duration.num_milliseconds();
+    let (sign, milliseconds) = if milliseconds < 0 { ("-", -milliseconds) } else { ("", milliseconds) };
+    let seconds = format!("{}.{:03}s", milliseconds / 1000 % 60, milliseconds % 1000);
+    match milliseconds / 1000 {
+        0 => format!("{}{}ms", sign, milliseconds),
+        1..=59 => format!("{}{}", sign, seconds),
+        60..=3599 => format!("{}{}m {}", sign, milliseconds / 60_000, seconds),
+        _ => format!(
+            "{}{}h {}m {}",
+            sign,
+            milliseconds / 3_600_000,
+            milliseconds / 60_000 % 60,
+            seconds
+        ),
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct StateProcessor {
     pub(crate) regex: Regex,
     pub(crate) color: Option<Color>,
+    pub(crate) field: Option<String>,
     pub(crate) last_state: Option<String>
 }
 
 impl StateProcessor {
     pub fn new(regex: Regex, color: Option<Color>) -> Self {
-        Self { regex, color, last_state: None }
+        Self { regex, color, field: None, last_state: None }
+    }
+
+    /// Matches the regex against the given field instead of the whole record
+    pub fn with_field(mut self, field: Option<String>) -> Self {
+        self.field = field;
+        self
     }
 }
 
 impl Processor for StateProcessor {
-    fn process_line(&mut self, line: &str) -> Option<String> {
-        if self.regex.is_match(line) {
+    fn process_record(&mut self, record: &Record) -> Option<String> {
+        let line = record.text.as_str();
+        if is_match(&self.regex, self.field.as_deref(), record) {
             self.last_state = Some(line.to_owned());
             let state = format!("State change:\n{}", line);
             Some(self.color.map(|color| color.paint(&state).to_string()).unwrap_or(state))
@@ -133,10 +640,56 @@ impl Processor for StateProcessor {
     }
 }
 
+/// Counts the occurrences of the values of a field, e.g. the status codes of an access log
+#[derive(Debug, Clone)]
+pub struct FieldCounter {
+    field: String,
+    counts: HashMap<String, usize>,
+}
+
+impl FieldCounter {
+    pub fn new(field: String) -> Self {
+        Self {
+            field,
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl Processor for FieldCounter {
+    fn process_record(&mut self, record: &Record) -> Option<String> {
+        if let Some(value) = record.field(&self.field) {
+            *self.counts.entry(value.to_owned()).or_default() += 1;
+        }
+        None
+    }
+
+    fn result(&self) -> Option<String> {
+        if self.counts.is_empty() {
+            return None;
+        }
+
+        let counts = self
+            .counts
+            .iter()
+            .sorted_by(|(value, count), (other_value, other_count)| {
+                other_count.cmp(count).then_with(|| value.cmp(other_value))
+            })
+            .map(|(value, count)| format!("{:>8} {}", count, value))
+            .join("\n");
+        Some(format!("Counts of {}:\n{}", self.field, counts))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Colorize, EventPatterns, EventProcessor, PatternColor, Processor, StateProcessor};
+    use super::{
+        format_duration, parse_duration, Colorize, EventPatterns, EventProcessor, EventTimeout, FieldCounter,
+        PatternColor, Processor, StateProcessor,
+    };
+    use crate::record::Record;
     use ansi_term::Color;
+    use chrono::{Duration, NaiveDateTime};
     use regex::Regex;
     use std::{
         fs::File,
@@ -144,21 +697,24 @@ mod tests {
         path::PathBuf,
     };
 
-    const DATE_REGEX_STR: &'static str = r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2}";
+    const DATE_REGEX_STR: &str = r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2}";
 
     fn create_level_colors() -> Vec<PatternColor> {
         vec![
             PatternColor {
                 regex: Regex::new(format!("{} INFO ", DATE_REGEX_STR).as_str()).unwrap(),
                 color: Color::Fixed(28),
+                field: None,
             },
             PatternColor {
                 regex: Regex::new(format!("{} WARN ", DATE_REGEX_STR).as_str()).unwrap(),
                 color: Color::Fixed(24),
+                field: None,
             },
             PatternColor {
                 regex: Regex::new(format!("{} ERROR ", DATE_REGEX_STR).as_str()).unwrap(),
                 color: Color::Fixed(88),
+                field: None,
             },
         ]
     }
@@ -273,18 +829,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_colorize_fields() {
+        let mut colorize = Colorize::new(vec![PatternColor {
+            regex: Regex::new("^ERROR$").unwrap(),
+            color: Color::Fixed(88),
+            field: Some("level".to_owned()),
+        }]);
+
+        let mut record = Record::new("2020-01-01 10:00:50 ERROR Failed to start application");
+        record.fields.insert("level".to_owned(), "ERROR".to_owned());
+        assert_eq!(
+            colorize.process_record(&record),
+            Some(Color::Fixed(88).paint(record.text.as_str()).to_string())
+        );
+
+        // The level field is missing, so the pattern doesn't match even though the text contains "ERROR"
+        let mut colorize = Colorize::new(colorize.pattern_colors);
+        assert_eq!(
+            colorize.process_record(&Record::new("ERROR")),
+            Some(Color::White.paint("ERROR").to_string())
+        );
+    }
+
     #[test]
     fn test_events() {
         let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
         let file = File::open(test_log_path).unwrap();
         let reader = BufReader::new(file);
         let mut lines = reader.lines();
-        let mut events = EventProcessor::new(EventPatterns {
-            start_regex: Regex::new(format!(r"{} INFO Mouse left down at [\d]+, [\d]+", DATE_REGEX_STR).as_str())
-                .unwrap(),
-            end_regex: Regex::new(format!(r"{} INFO Mouse left up at [\d]+, [\d]+", DATE_REGEX_STR).as_str()).unwrap(),
-            color: Some(Color::Fixed(28)),
-        });
+        let mut events = EventProcessor::new(
+            EventPatterns::new(
+                Regex::new(format!(r"{} INFO Mouse left down at [\d]+, [\d]+", DATE_REGEX_STR).as_str()).unwrap(),
+                Regex::new(format!(r"{} INFO Mouse left up at [\d]+, [\d]+", DATE_REGEX_STR).as_str()).unwrap(),
+            )
+            .with_color(Some(Color::Fixed(28))),
+        );
 
         for line in &mut lines {
             if let Some(event) = events.process_line(line.unwrap().as_str()) {
@@ -320,6 +900,224 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_correlated_events() {
+        let mut events = EventProcessor::new(
+            EventPatterns::new(
+                Regex::new(r"\[(?P<request_id>\w+)\] Request started").unwrap(),
+                Regex::new(r"\[(?P<request_id>\w+)\] Request finished").unwrap(),
+            )
+            .with_key(Some("request_id".to_owned())),
+        );
+        assert!(events.event_patterns.has_key_captures());
+
+        assert_eq!(events.process_line("2020-01-01 10:00:00 INFO [a1] Request started"), None);
+        assert_eq!(events.process_line("2020-01-01 10:00:01 INFO [b2] Request started"), None);
+        assert_eq!(events.process_line("2020-01-01 10:00:02 WARN Slow query"), None);
+        assert_eq!(
+            events.process_line("2020-01-01 10:00:03 INFO [b2] Request finished").as_deref(),
+            Some(concat!(
+                "Event request_id=b2:\n",
+                "2020-01-01 10:00:01 INFO [b2] Request started\n",
+                "2020-01-01 10:00:02 WARN Slow query\n",
+                "2020-01-01 10:00:03 INFO [b2] Request finished"
+            ))
+        );
+        assert_eq!(events.process_line("2020-01-01 10:00:04 INFO [c3] Request finished"), None);
+
+        // Lines with the key as a field only belong to the event with the same key
+        let mut record = Record::new("2020-01-01 10:00:05 INFO Query done");
+        record.fields.insert("request_id".to_owned(), "c3".to_owned());
+        assert_eq!(events.process_record(&record), None);
+        // The line without a key belongs to the most recently started event only
+        assert_eq!(
+            events.process_line("2020-01-01 10:00:06 INFO [a1] Request finished").as_deref(),
+            Some(concat!(
+                "Event request_id=a1:\n",
+                "2020-01-01 10:00:00 INFO [a1] Request started\n",
+                "2020-01-01 10:00:06 INFO [a1] Request finished"
+            ))
+        );
+
+        let events = EventProcessor::new(EventPatterns {
+            end_regex: Regex::new("Request finished").unwrap(),
+            ..events.event_patterns
+        });
+        assert!(!events.event_patterns.has_key_captures());
+    }
+
+    #[test]
+    fn test_incomplete_events() {
+        let mut events = EventProcessor::new(
+            EventPatterns::new(
+                Regex::new(r"\[(?P<request_id>\w+)\] Request started").unwrap(),
+                Regex::new(r"\[(?P<request_id>\w+)\] Request finished").unwrap(),
+            )
+            .with_key(Some("request_id".to_owned()))
+            .with_report_orphans(true),
+        );
+        assert_eq!(events.finish(), None);
+
+        assert_eq!(events.process_line("INFO [b2] Request started"), None);
+        assert_eq!(events.process_line("INFO [a1] Request started"), None);
+        assert_eq!(events.process_line("INFO [c3] Request finished"), None);
+        assert_eq!(events.process_line("WARN Slow query"), None);
+        assert_eq!(
+            events.finish().as_deref(),
+            Some(concat!(
+                "Incomplete event request_id=b2:\n",
+                "INFO [b2] Request started\n",
+                "Incomplete event request_id=a1:\n",
+                "INFO [a1] Request started\n",
+                "WARN Slow query\n",
+                "End lines without a start:\n",
+                "INFO [c3] Request finished"
+            ))
+        );
+        assert_eq!(events.finish(), None);
+    }
+
+    #[test]
+    fn test_event_durations() {
+        let mut events = EventProcessor::new(EventPatterns::new(
+            Regex::new("Mouse left down").unwrap(),
+            Regex::new("Mouse left up").unwrap(),
+        ));
+        let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
+        let events_output = BufReader::new(File::open(test_log_path).unwrap())
+            .lines()
+            .filter_map(|line| {
+                let mut record = Record::new(line.unwrap());
+                record.timestamp = record
+                    .text
+                    .get(..19)
+                    .and_then(|timestamp| NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S").ok());
+                events.process_record(&record)
+            })
+            .collect::<Vec<_>>();
+        assert!(events_output[0].starts_with("Event (2.000s):\n"));
+        assert!(events_output[1].starts_with("Event (1.000s):\n"));
+
+        assert_eq!(
+            events.result().as_deref(),
+            Some(concat!(
+                "Durations of events from \"Mouse left down\" to \"Mouse left up\":\n",
+                "   count 2\n",
+                "     min 1.000s\n",
+                "     max 2.000s\n",
+                "    mean 1.500s\n",
+                "     p50 1.000s\n",
+                "     p90 2.000s\n",
+                "     p99 2.000s"
+            ))
+        );
+
+        assert_eq!(format_duration(Duration::milliseconds(850)), "850ms");
+        assert_eq!(format_duration(Duration::milliseconds(3_725_250)), "1h 2m 5.250s");
+    }
+
+    #[test]
+    fn test_event_timeouts() {
+        let event_patterns =
+            EventPatterns::new(Regex::new("Request started").unwrap(), Regex::new("Request finished").unwrap());
+        let mut events = EventProcessor::new(
+            event_patterns
+                .clone()
+                .with_timeout(Some(EventTimeout::Duration(Duration::seconds(30)))),
+        );
+        let mut process_line = |line: &str| {
+            let mut record = Record::new(line);
+            record.timestamp = NaiveDateTime::parse_from_str(&line[..19], "%Y-%m-%d %H:%M:%S").ok();
+            events.process_record(&record)
+        };
+        assert_eq!(process_line("2020-01-01 10:00:00 Request started"), None);
+        assert_eq!(process_line("2020-01-01 10:00:30 Query"), None);
+        // The line after the timeout starts a new event
+        assert_eq!(
+            process_line("2020-01-01 10:00:31 Request started").as_deref(),
+            Some(concat!(
+                "Timed out event (after 30.000s):\n",
+                "2020-01-01 10:00:00 Request started\n",
+                "2020-01-01 10:00:30 Query"
+            ))
+        );
+        assert!(process_line("2020-01-01 10:00:32 Request finished").is_some_and(|event| event.starts_with("Event")));
+
+        let mut events = EventProcessor::new(event_patterns.clone().with_timeout(Some(EventTimeout::Lines(2))));
+        assert_eq!(events.process_line("Request started"), None);
+        assert_eq!(events.process_line("Query"), None);
+        assert_eq!(events.process_line("Query"), None);
+        assert_eq!(
+            events.process_line("Request finished").as_deref(),
+            Some("Timed out event (after 2 lines):\nRequest started\nQuery\nQuery")
+        );
+
+        // Capped events are reported differently from the events timed out after the same number of lines
+        let mut events = EventProcessor::new(event_patterns.with_max_lines(Some(2)));
+        assert_eq!(events.process_line("Request started"), None);
+        assert_eq!(
+            events.process_line("Query").as_deref(),
+            Some("Truncated event (max_lines 2):\nRequest started\nQuery")
+        );
+        assert_eq!(events.process_line("Request finished"), None);
+        assert_eq!(events.finish(), None);
+
+        assert_eq!("100".parse(), Ok(EventTimeout::Lines(100)));
+        assert_eq!("1m 30s".parse(), Ok(EventTimeout::Duration(Duration::seconds(90))));
+        assert!("30 days".parse::<EventTimeout>().is_err());
+        assert_eq!(parse_duration("1h 2m 5.250s"), Some(Duration::milliseconds(3_725_250)));
+        assert_eq!(parse_duration("850ms"), Some(Duration::milliseconds(850)));
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_nested_events() {
+        let event_patterns = |start: &str, end: &str, children| {
+            EventPatterns::new(Regex::new(start).unwrap(), Regex::new(end).unwrap()).with_children(children)
+        };
+        let query = event_patterns("Query start", "Query end", Vec::new());
+        let request = event_patterns("Request started", "Request finished", vec![query]);
+        let mut events = EventProcessor::new(event_patterns("Transaction begin", "Transaction commit", vec![request]));
+        let mut process_line = |line: &str| {
+            let mut record = Record::new(line);
+            let timestamp = format!("2020-01-01 {}", &line[..8]);
+            record.timestamp = NaiveDateTime::parse_from_str(&timestamp, "%Y-%m-%d %H:%M:%S").ok();
+            events.process_record(&record)
+        };
+
+        // Child events are only recognized inside their parent events
+        assert_eq!(process_line("10:00:00 Request started"), None);
+        assert_eq!(process_line("10:00:01 Transaction begin"), None);
+        assert_eq!(process_line("10:00:02 Request started"), None);
+        assert_eq!(process_line("10:00:03 Query start"), None);
+        assert_eq!(process_line("10:00:04 Query end"), None);
+        assert_eq!(process_line("10:00:05 Request finished"), None);
+        assert_eq!(process_line("10:00:06 Cleanup"), None);
+        assert_eq!(process_line("10:00:07 Request started"), None);
+        assert_eq!(
+            process_line("10:00:09 Transaction commit").as_deref(),
+            Some(concat!(
+                "Event (8.000s):\n",
+                "  10:00:01 Transaction begin\n",
+                "  Event (3.000s):\n",
+                "    10:00:02 Request started\n",
+                "    Event (1.000s):\n",
+                "      10:00:03 Query start\n",
+                "      10:00:04 Query end\n",
+                "    10:00:05 Request finished\n",
+                "  10:00:06 Cleanup\n",
+                "  Incomplete event:\n",
+                "    10:00:07 Request started\n",
+                "  10:00:09 Transaction commit"
+            ))
+        );
+
+        let result = events.result().unwrap();
+        assert!(result.starts_with("Durations of events from \"Transaction begin\" to \"Transaction commit\":\n"));
+        assert!(result.contains("Durations of events from \"Request started\" to \"Request finished\":\n   count 1\n"));
+        assert!(result.contains("Durations of events from \"Query start\" to \"Query end\":\n   count 1\n"));
+    }
+
     #[test]
     fn test_states() {
         let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
@@ -355,4 +1153,22 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_field_counter() {
+        let mut counter = FieldCounter::new("status".to_owned());
+        assert_eq!(counter.result(), None);
+
+        for status in &["200", "404", "200", "500", "200", "404"] {
+            let mut record = Record::new(format!("GET / {}", status));
+            record.fields.insert("status".to_owned(), (*status).to_owned());
+            assert_eq!(counter.process_record(&record), None);
+        }
+        assert_eq!(counter.process_line("A line without a status"), None);
+
+        assert_eq!(
+            counter.result().as_deref(),
+            Some("Counts of status:\n       3 200\n       2 404\n       1 500")
+        );
+    }
 }
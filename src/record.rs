@@ -0,0 +1,391 @@
+use crate::{
+    formats::{ContainerFormat, InputFormat, Template},
+    processors::EventPatterns,
+    timestamp::{Timestamp, TimestampParser},
+};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// A log record: a line with its continuation lines and the fields extracted from it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Record {
+    pub text: String,
+    pub fields: HashMap<String, String>,
+    pub timestamp: Option<Timestamp>,
+}
+
+impl Record {
+    #[cfg(test)]
+    pub fn new<T: Into<String>>(text: T) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|value| value.as_str())
+    }
+
+    /// Returns the text that patterns are matched against: the value of the field if given, otherwise the whole text
+    pub fn target(&self, field: Option<&str>) -> Option<&str> {
+        match field {
+            Some(field) => self.field(field),
+            None => Some(self.text.as_str()),
+        }
+    }
+}
+
+/// Creates records from their text by extracting the fields and parsing the timestamp. Fields are extracted by the
+/// input format, or from the named capture groups of the prefix for text input. The named capture groups of the event
+/// patterns matching a record are fields as well. Records of structured input formats are displayed with the template
+/// if there's one. The timestamp is parsed from the displayed text unless the timestamp parser has a field.
+#[derive(Debug, Clone, Default)]
+pub struct RecordParser {
+    fields_regex: Option<Regex>,
+    /// The event patterns with named capture groups and the fields they are matched against
+    event_regexes: Vec<(Option<String>, Regex)>,
+    timestamp_parser: Option<TimestampParser>,
+    input_format: InputFormat,
+    template: Option<Template>,
+    container_format: Option<ContainerFormat>,
+}
+
+impl RecordParser {
+    pub fn new(prefix: Option<&str>, timestamp_parser: Option<TimestampParser>) -> Result<Self, regex::Error> {
+        let fields_regex = prefix
+            .map(|prefix| Regex::new(&format!("^(?:{})", prefix)))
+            .transpose()?
+            .filter(|regex| regex.capture_names().any(|name| name.is_some()));
+        Ok(Self {
+            fields_regex,
+            timestamp_parser,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_input_format(mut self, input_format: InputFormat, template: Option<Template>) -> Self {
+        self.input_format = input_format;
+        self.template = template;
+        self
+    }
+
+    /// Sets the container log format, which is needed to parse the timestamps of raw lines
+    pub fn with_container_format(mut self, container_format: Option<ContainerFormat>) -> Self {
+        self.container_format = container_format;
+        self
+    }
+
+    /// Extracts the named capture groups of the start and end patterns of the events and of their child events
+    pub fn with_event_patterns(mut self, event_patterns: &[EventPatterns]) -> Self {
+        for event_patterns in event_patterns {
+            for regex in &[&event_patterns.start_regex, &event_patterns.end_regex] {
+                if regex.capture_names().any(|name| name.is_some()) {
+                    self.event_regexes.push((event_patterns.field.clone(), (*regex).clone()));
+                }
+            }
+            self = self.with_event_patterns(&event_patterns.children);
+        }
+        self
+    }
+
+    #[cfg(test)]
+    pub fn parse(&self, text: String) -> Record {
+        self.parse_with_fields(text, HashMap::new())
+    }
+
+    /// Parses a record that already has some fields, e.g. the fields of the container runtime. The fields extracted
+    /// from the text take precedence over them, and over the captures of the event patterns.
+    pub fn parse_with_fields(&self, text: String, fields: HashMap<String, String>) -> Record {
+        let mut record = self.parse_text_fields(text, fields);
+        self.add_event_fields(&mut record);
+        record
+    }
+
+    fn parse_text_fields(&self, text: String, mut fields: HashMap<String, String>) -> Record {
+        match self.input_format.parse_fields(&text) {
+            Some(parsed_fields) => {
+                fields.extend(parsed_fields);
+                let text = match &self.template {
+                    Some(template) => template.render(&fields),
+                    None => text,
+                };
+                let timestamp = self.parse_timestamp_with_fields(&text, &fields);
+                Record {
+                    text,
+                    fields,
+                    timestamp,
+                }
+            }
+            None => {
+                fields.extend(self.parse_prefix_fields(&text));
+                let timestamp = self.parse_timestamp_with_fields(&text, &fields);
+                Record {
+                    text,
+                    fields,
+                    timestamp,
+                }
+            }
+        }
+    }
+
+    /// Parses only the timestamp of a line
+    pub fn parse_timestamp(&self, line: &str) -> Option<Timestamp> {
+        let timestamp_parser = self.timestamp_parser.as_ref()?;
+        if let Some(container_line) = self.container_format.and_then(|format| format.decode(line)) {
+            self.parse_with_fields(container_line.text, container_line.fields).timestamp
+        } else if timestamp_parser.field.is_some() || self.template.is_some() {
+            self.parse_with_fields(line.to_owned(), HashMap::new()).timestamp
+        } else {
+            timestamp_parser.parse(line)
+        }
+    }
+
+    fn parse_timestamp_with_fields(&self, text: &str, fields: &HashMap<String, String>) -> Option<Timestamp> {
+        let timestamp_parser = self.timestamp_parser.as_ref()?;
+        match &timestamp_parser.field {
+            Some(field) => timestamp_parser.parse(fields.get(field)?),
+            None => timestamp_parser.parse(text),
+        }
+    }
+
+    fn parse_prefix_fields(&self, text: &str) -> HashMap<String, String> {
+        match &self.fields_regex {
+            Some(regex) => named_captures(regex, text).collect(),
+            None => HashMap::new(),
+        }
+    }
+
+    /// Adds the captures of the event patterns that match the record, without overriding the fields it has
+    fn add_event_fields(&self, record: &mut Record) {
+        let event_fields = self
+            .event_regexes
+            .iter()
+            .filter_map(|(field, regex)| Some(named_captures(regex, record.target(field.as_deref())?)))
+            .flatten()
+            .collect::<Vec<_>>();
+        for (name, value) in event_fields {
+            record.fields.entry(name).or_insert(value);
+        }
+    }
+}
+
+/// Returns the values of the named capture groups of the regex if it matches the text
+fn named_captures<'a>(regex: &'a Regex, text: &'a str) -> impl Iterator<Item = (String, String)> + 'a {
+    let captures = regex.captures(text);
+    regex.capture_names().flatten().filter_map(move |name| {
+        let value = captures.as_ref()?.name(name)?;
+        Some((name.to_owned(), value.as_str().to_owned()))
+    })
+}
+
+/// Joins continuation lines (e.g. stack traces) to the preceding line, so processors get whole records. A line is a
+/// continuation if it doesn't match the record start regex or it matches the continuation regex.
+#[derive(Debug, Clone)]
+pub struct RecordAssembler {
+    start_regex: Option<Regex>,
+    continuation_regex: Option<Regex>,
+    current_record: Option<String>,
+}
+
+impl RecordAssembler {
+    pub fn new(start_regex: Option<Regex>, continuation_regex: Option<Regex>) -> Self {
+        Self {
+            start_regex,
+            continuation_regex,
+            current_record: None,
+        }
+    }
+
+    /// Creates an assembler where records start with lines matching `start_pattern` at their beginning
+    pub fn from_patterns(
+        start_pattern: Option<&str>,
+        continuation_pattern: Option<&str>,
+    ) -> Result<Self, regex::Error> {
+        let start_regex = start_pattern
+            .map(|pattern| Regex::new(&format!("^(?:{})", pattern)))
+            .transpose()?;
+        let continuation_regex = continuation_pattern.map(Regex::new).transpose()?;
+        Ok(Self::new(start_regex, continuation_regex))
+    }
+
+    fn is_continuation(&self, line: &str) -> bool {
+        self.continuation_regex.as_ref().is_some_and(|regex| regex.is_match(line))
+            || self.start_regex.as_ref().is_some_and(|regex| !regex.is_match(line))
+    }
+
+    /// Adds a line to the current record. Returns the previous record if the line starts a new one.
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        let is_continuation = self.is_continuation(line);
+        match &mut self.current_record {
+            Some(record) if is_continuation => {
+                record.push('\n');
+                record.push_str(line);
+                None
+            }
+            _ => self.current_record.replace(line.to_owned()),
+        }
+    }
+
+    /// Returns the record that is being assembled
+    pub fn flush(&mut self) -> Option<String> {
+        self.current_record.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordAssembler, RecordParser};
+    use crate::{
+        formats::{ContainerFormat, InputFormat},
+        processors::EventPatterns,
+        timestamp::{TimestampFormat, TimestampParser},
+    };
+    use chrono::NaiveDate;
+    use regex::Regex;
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader},
+        path::PathBuf,
+    };
+
+    #[test]
+    fn test_parse_record_fields() {
+        let parser = RecordParser::new(
+            Some(r"(?P<date>[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2}) (?P<level>\w+) "),
+            Some(TimestampParser::new(TimestampFormat::Iso8601, None)),
+        )
+        .unwrap();
+
+        let record = parser.parse("2020-01-01 10:00:50 ERROR Failed to start application".to_owned());
+        assert_eq!(record.field("level"), Some("ERROR"));
+        assert_eq!(record.field("date"), Some("2020-01-01 10:00:50"));
+        assert_eq!(record.target(Some("level")), Some("ERROR"));
+        assert_eq!(record.target(None), Some("2020-01-01 10:00:50 ERROR Failed to start application"));
+        assert_eq!(
+            record.timestamp,
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(10, 0, 50)
+        );
+
+        let record = parser.parse("An unknown error occurred".to_owned());
+        assert!(record.fields.is_empty());
+        assert_eq!(record.target(Some("level")), None);
+        assert_eq!(record.timestamp, None);
+    }
+
+    #[test]
+    fn test_parse_event_fields() {
+        let event_patterns = EventPatterns::new(
+            Regex::new(r"(?P<level>\w+) User (?P<user>\w+) logged in").unwrap(),
+            Regex::new(r"User (?P<user>\w+) logged out").unwrap(),
+        );
+        let parser = RecordParser::new(Some(r"(?P<level>\w+) "), None)
+            .unwrap()
+            .with_event_patterns(&[event_patterns]);
+
+        let record = parser.parse("INFO User alice logged in".to_owned());
+        assert_eq!(record.field("user"), Some("alice"));
+        assert_eq!(record.field("level"), Some("INFO"));
+        let record = parser.parse("INFO User alice logged out".to_owned());
+        assert_eq!(record.field("user"), Some("alice"));
+
+        // The fields of the prefix are not overridden
+        let record = parser.parse("DEBUG WARN User bob logged in".to_owned());
+        assert_eq!(record.field("user"), Some("bob"));
+        assert_eq!(record.field("level"), Some("DEBUG"));
+
+        let record = parser.parse("INFO Set state to main_menu".to_owned());
+        assert_eq!(record.field("user"), None);
+    }
+
+    #[test]
+    fn test_parse_json_record() {
+        let timestamp_parser = TimestampParser::new(TimestampFormat::Rfc3339, None).with_field(Some("ts".to_owned()));
+        let parser = RecordParser::new(None, Some(timestamp_parser))
+            .unwrap()
+            .with_input_format(InputFormat::Json, Some("{ts} {level} {msg}".parse().unwrap()));
+
+        let line = r#"{"ts":"2020-01-01T10:00:50Z","level":"ERROR","msg":"Failed to start application"}"#;
+        let record = parser.parse(line.to_owned());
+        assert_eq!(record.text, "2020-01-01T10:00:50Z ERROR Failed to start application");
+        assert_eq!(record.field("level"), Some("ERROR"));
+        assert_eq!(
+            record.timestamp,
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(10, 0, 50)
+        );
+        assert_eq!(parser.parse_timestamp(line), record.timestamp);
+
+        let record = parser.parse("An unknown error occurred".to_owned());
+        assert_eq!(record.text, "An unknown error occurred");
+        assert!(record.fields.is_empty());
+    }
+
+    #[test]
+    fn test_parse_container_record() {
+        let timestamp_parser = TimestampParser::new(TimestampFormat::Rfc3339, None).with_field(Some("time".to_owned()));
+        let parser = RecordParser::new(Some(r"(?P<level>\w+) "), Some(timestamp_parser))
+            .unwrap()
+            .with_container_format(Some(ContainerFormat::Cri));
+
+        let line = "2020-01-01T10:00:50.000000001Z stderr F ERROR Failed to start application";
+        let container_line = ContainerFormat::Cri.decode(line).unwrap();
+        let record = parser.parse_with_fields(container_line.text, container_line.fields);
+        assert_eq!(record.text, "ERROR Failed to start application");
+        assert_eq!(record.field("level"), Some("ERROR"));
+        assert_eq!(record.field("stream"), Some("stderr"));
+        assert_eq!(
+            record.timestamp,
+            NaiveDate::from_ymd_opt(2020, 1, 1)
+                .unwrap()
+                .and_hms_nano_opt(10, 0, 50, 1)
+        );
+        assert_eq!(parser.parse_timestamp(line), record.timestamp);
+    }
+
+    #[test]
+    fn test_assemble_records_by_prefix() {
+        let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
+        let reader = BufReader::new(File::open(test_log_path).unwrap());
+        let mut assembler =
+            RecordAssembler::from_patterns(Some(r"[\d]{4}-[\d]{2}-[\d]{2} [\d]{2}:[\d]{2}:[\d]{2} "), None).unwrap();
+
+        let mut records = reader
+            .lines()
+            .filter_map(|line| assembler.push_line(&line.unwrap()))
+            .collect::<Vec<_>>();
+        records.extend(assembler.flush());
+
+        assert_eq!(records.len(), 12);
+        assert_eq!(
+            records[10],
+            "2020-01-01 10:00:50 ERROR Failed to start application\nAn unknown error occurred"
+        );
+        assert_eq!(records[11], "2020-01-01 10:01:00 INFO End of log file");
+    }
+
+    #[test]
+    fn test_assemble_records_by_continuation() {
+        let mut assembler = RecordAssembler::from_patterns(None, Some(r"^\tat ")).unwrap();
+        let lines = vec![
+            "ERROR Unhandled exception",
+            "java.lang.NullPointerException",
+            "\tat Main.main(Main.java:1)",
+            "INFO Exiting",
+        ];
+
+        let mut records = lines
+            .into_iter()
+            .filter_map(|line| assembler.push_line(line))
+            .collect::<Vec<_>>();
+        records.extend(assembler.flush());
+
+        assert_eq!(
+            records,
+            vec![
+                "ERROR Unhandled exception",
+                "java.lang.NullPointerException\n\tat Main.main(Main.java:1)",
+                "INFO Exiting",
+            ]
+        );
+    }
+}
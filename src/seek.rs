@@ -0,0 +1,114 @@
+use crate::{record::RecordParser, timestamp::Timestamp};
+use std::io::{self, BufRead, Seek, SeekFrom};
+
+/// Finds the offset of the first line with a timestamp not earlier than `since` by binary search over the byte
+/// offsets. The lines are expected to be sorted by their timestamps. Returns the length of the data if all the
+/// timestamps are earlier, and `None` if no line has a timestamp the parser understands, so the caller can't skip
+/// anything.
+pub fn find_offset<R: BufRead + Seek>(
+    reader: &mut R,
+    parser: &RecordParser,
+    since: Timestamp,
+) -> io::Result<Option<u64>> {
+    let length = reader.seek(SeekFrom::End(0))?;
+    let mut low = 0;
+    let mut high = length;
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match first_timestamp_from(reader, parser, middle)? {
+            Some((_, timestamp)) if timestamp < since => low = middle + 1,
+            _ => high = middle,
+        }
+    }
+
+    match first_timestamp_from(reader, parser, low)? {
+        Some((offset, _)) => Ok(Some(offset)),
+        // The timestamps before `low` are earlier than `since`, unless there are none at all
+        None if low > 0 => Ok(Some(length)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the offset and the timestamp of the first line with a timestamp that starts at or after `offset`
+fn first_timestamp_from<R: BufRead + Seek>(
+    reader: &mut R,
+    parser: &RecordParser,
+    offset: u64,
+) -> io::Result<Option<(u64, Timestamp)>> {
+    let mut line_start = offset;
+    let mut line = Vec::new();
+    if offset > 0 {
+        // Skip to the start of the next line. Checking the previous byte handles offsets right at a line start
+        reader.seek(SeekFrom::Start(offset - 1))?;
+        line_start = offset - 1 + reader.read_until(b'\n', &mut line)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        if let Some(timestamp) = parser.parse_timestamp(&String::from_utf8_lossy(&line)) {
+            return Ok(Some((line_start, timestamp)));
+        }
+        line_start += read as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_offset;
+    use crate::{
+        record::RecordParser,
+        timestamp::{parse_time_bound, TimestampFormat, TimestampParser},
+    };
+    use std::{
+        fs,
+        io::{BufReader, Cursor},
+        path::PathBuf,
+    };
+
+    #[test]
+    fn test_find_offset() {
+        let test_log_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test.log");
+        let log = fs::read_to_string(test_log_path).unwrap();
+        let parser = RecordParser::new(None, Some(TimestampParser::new(TimestampFormat::Iso8601, None))).unwrap();
+        let mut reader = BufReader::new(Cursor::new(log.as_bytes()));
+
+        let find = |reader: &mut BufReader<Cursor<&[u8]>>, since: &str| {
+            let offset = find_offset(reader, &parser, parse_time_bound(since).unwrap()).unwrap().unwrap() as usize;
+            log[offset..].lines().next().map(|line| line.to_owned())
+        };
+
+        assert_eq!(
+            find(&mut reader, "2020-01-01 00:00:00").as_deref(),
+            Some("2020-01-01 10:00:00 INFO Start of log file")
+        );
+        assert_eq!(
+            find(&mut reader, "2020-01-01 10:00:03").as_deref(),
+            Some("2020-01-01 10:00:03 INFO Mouse left up at 10, 0")
+        );
+        assert_eq!(
+            find(&mut reader, "2020-01-01 10:00:04.500").as_deref(),
+            Some("2020-01-01 10:00:05 INFO Mouse left up at 10, 10")
+        );
+        assert_eq!(
+            find(&mut reader, "2020-01-01 10:00:51").as_deref(),
+            Some("2020-01-01 10:01:00 INFO End of log file")
+        );
+        assert_eq!(find(&mut reader, "2020-01-02").as_deref(), None);
+    }
+
+    #[test]
+    fn test_find_offset_without_timestamps() {
+        let log = "[2020-01-01 10:00:00] INFO First\n[2020-01-01 10:00:01] INFO Second\n";
+        let parser = RecordParser::new(None, Some(TimestampParser::new(TimestampFormat::Iso8601, None))).unwrap();
+        let mut reader = BufReader::new(Cursor::new(log.as_bytes()));
+        let since = parse_time_bound("2020-01-01 10:00:01").unwrap();
+        assert_eq!(find_offset(&mut reader, &parser, since).unwrap(), None);
+    }
+}
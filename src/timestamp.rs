@@ -0,0 +1,252 @@
+use chrono::{format::Item, format::StrftimeItems, DateTime, Datelike, Local, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::str::FromStr;
+
+/// The timestamp of a log line. Timestamps with a time zone offset are converted to UTC, others are used as they are.
+pub type Timestamp = NaiveDateTime;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimestampFormat {
+    /// E.g. `2020-01-01 10:00:00`, `2020-01-01T10:00:00.123+02:00`
+    Iso8601,
+    /// E.g. `2020-01-01T10:00:00Z`
+    Rfc3339,
+    /// E.g. `Jan  1 10:00:00`. The current year is assumed since it's not part of the timestamp
+    Syslog,
+    /// Milliseconds since the Unix epoch
+    EpochMillis,
+    /// Seconds since the Unix epoch
+    EpochSeconds,
+    /// A strftime-style format, e.g. `%d/%m/%Y %H:%M:%S`
+    Custom(String),
+}
+
+impl FromStr for TimestampFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "iso8601" => Ok(TimestampFormat::Iso8601),
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            "syslog" => Ok(TimestampFormat::Syslog),
+            "epoch_millis" => Ok(TimestampFormat::EpochMillis),
+            "epoch_seconds" => Ok(TimestampFormat::EpochSeconds),
+            format if format.contains('%') => {
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    Err(format!("Invalid timestamp format: {}", format))
+                } else {
+                    Ok(TimestampFormat::Custom(format.to_owned()))
+                }
+            }
+            format => Err(format!("Unknown timestamp format: {}", format)),
+        }
+    }
+}
+
+/// Parses the timestamps of lines. By default the timestamp is expected at the start of the line. If a pattern is
+/// given, the timestamp is taken from its `timestamp` named capture group, the first capture group or the whole
+/// match, in this order. If a field is given, the timestamp is parsed from the value of that field of the records.
+#[derive(Debug, Clone)]
+pub struct TimestampParser {
+    pub(crate) format: TimestampFormat,
+    pub(crate) regex: Option<Regex>,
+    pub(crate) field: Option<String>,
+}
+
+impl TimestampParser {
+    pub fn new(format: TimestampFormat, regex: Option<Regex>) -> Self {
+        Self {
+            format,
+            regex,
+            field: None,
+        }
+    }
+
+    pub fn with_field(mut self, field: Option<String>) -> Self {
+        self.field = field;
+        self
+    }
+
+    pub fn parse(&self, line: &str) -> Option<Timestamp> {
+        match &self.regex {
+            Some(regex) => {
+                let captures = regex.captures(line)?;
+                let text = captures
+                    .name("timestamp")
+                    .or_else(|| captures.get(1))
+                    .or_else(|| captures.get(0))?
+                    .as_str();
+                self.parse_start(text)
+            }
+            None => self.parse_start(line),
+        }
+    }
+
+    /// Parses the timestamp at the start of the text, ignoring anything after it
+    fn parse_start(&self, text: &str) -> Option<Timestamp> {
+        match &self.format {
+            TimestampFormat::Iso8601 => parse_iso8601(text),
+            TimestampFormat::Rfc3339 => {
+                let token = text.split_whitespace().next()?;
+                DateTime::parse_from_rfc3339(token)
+                    .ok()
+                    .map(|timestamp| timestamp.naive_utc())
+            }
+            TimestampFormat::Syslog => {
+                let text = format!("{} {}", Local::now().year(), text);
+                NaiveDateTime::parse_and_remainder(&text, "%Y %b %e %H:%M:%S")
+                    .ok()
+                    .map(|(timestamp, _)| timestamp)
+            }
+            TimestampFormat::EpochMillis => DateTime::from_timestamp_millis(leading_digits(text)?.parse().ok()?)
+                .map(|timestamp| timestamp.naive_utc()),
+            TimestampFormat::EpochSeconds => {
+                DateTime::from_timestamp(leading_digits(text)?.parse().ok()?, 0).map(|timestamp| timestamp.naive_utc())
+            }
+            TimestampFormat::Custom(format) => DateTime::parse_and_remainder(text, format)
+                .map(|(timestamp, _)| timestamp.naive_utc())
+                .or_else(|_| NaiveDateTime::parse_and_remainder(text, format).map(|(timestamp, _)| timestamp))
+                .ok(),
+        }
+    }
+}
+
+fn leading_digits(text: &str) -> Option<&str> {
+    let end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    if end == 0 {
+        None
+    } else {
+        Some(&text[..end])
+    }
+}
+
+/// Parses an ISO 8601 timestamp, with either `T` or space between the date and the time and with optional fractional
+/// seconds and time zone offset
+pub fn parse_iso8601(text: &str) -> Option<Timestamp> {
+    if let Ok((timestamp, _)) = DateTime::parse_and_remainder(text, "%Y-%m-%dT%H:%M:%S%.f%#z")
+        .or_else(|_| DateTime::parse_and_remainder(text, "%Y-%m-%d %H:%M:%S%.f%#z"))
+    {
+        return Some(timestamp.naive_utc());
+    }
+
+    NaiveDateTime::parse_and_remainder(text, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_and_remainder(text, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()
+        .map(|(timestamp, _)| timestamp)
+}
+
+/// Parses the value of the `--since` and `--until` arguments. Accepts ISO 8601 timestamps and dates.
+pub fn parse_time_bound(value: &str) -> Option<Timestamp> {
+    parse_iso8601(value).or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    pub since: Option<Timestamp>,
+    pub until: Option<Timestamp>,
+}
+
+impl TimeRange {
+    pub fn contains(&self, timestamp: Timestamp) -> bool {
+        self.since.is_none_or(|since| timestamp >= since) && self.until.is_none_or(|until| timestamp <= until)
+    }
+}
+
+/// Drops the records outside of a time range. Records without a timestamp are kept if the last record with a
+/// timestamp was kept, so continuation lines stay together with their line.
+#[derive(Debug, Clone)]
+pub struct TimeFilter {
+    range: TimeRange,
+    last_accepted: bool,
+}
+
+impl TimeFilter {
+    pub fn new(range: TimeRange) -> Self {
+        Self {
+            range,
+            last_accepted: true,
+        }
+    }
+
+    pub fn accepts(&mut self, timestamp: Option<Timestamp>) -> bool {
+        if let Some(timestamp) = timestamp {
+            self.last_accepted = self.range.contains(timestamp);
+        }
+
+        self.last_accepted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_time_bound, TimeFilter, TimeRange, TimestampFormat, TimestampParser};
+    use chrono::{Datelike, Local, NaiveDate};
+    use regex::Regex;
+
+    #[test]
+    fn test_parse_timestamp_formats() {
+        let expected = NaiveDate::from_ymd_opt(2020, 1, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+
+        let parser = TimestampParser::new(TimestampFormat::Iso8601, None);
+        assert_eq!(
+            parser.parse("2020-01-01 10:00:00 INFO Start of log file"),
+            Some(expected)
+        );
+        assert_eq!(parser.parse("2020-01-01T12:00:00.000+02:00 INFO Start"), Some(expected));
+        assert_eq!(parser.parse("An unknown error occurred"), None);
+
+        let parser = TimestampParser::new(TimestampFormat::Rfc3339, None);
+        assert_eq!(parser.parse("2020-01-01T10:00:00Z INFO Start"), Some(expected));
+
+        let parser = TimestampParser::new(TimestampFormat::Syslog, None);
+        assert_eq!(
+            parser.parse("Jan  1 10:00:00 host app[123]: Start"),
+            Some(expected.with_year(Local::now().year()).unwrap())
+        );
+
+        let parser = TimestampParser::new(TimestampFormat::EpochMillis, None);
+        assert_eq!(parser.parse("1577872800000 INFO Start"), Some(expected));
+
+        let parser = TimestampParser::new(TimestampFormat::EpochSeconds, None);
+        assert_eq!(parser.parse("1577872800 INFO Start"), Some(expected));
+
+        let parser = TimestampParser::new(
+            "%d/%m/%Y:%H:%M:%S %z".parse().unwrap(),
+            Some(Regex::new(r"\[(?P<timestamp>[^\]]+)\]").unwrap()),
+        );
+        assert_eq!(
+            parser.parse(r#"127.0.0.1 - - [01/01/2020:11:00:00 +0100] "GET / HTTP/1.1" 200 512"#),
+            Some(expected)
+        );
+
+        assert!("unknown".parse::<TimestampFormat>().is_err());
+    }
+
+    #[test]
+    fn test_time_filter() {
+        let parser = TimestampParser::new(TimestampFormat::Iso8601, None);
+        let range = TimeRange {
+            since: parse_time_bound("2020-01-01 10:00:50"),
+            until: parse_time_bound("2020-01-01T10:00:59"),
+        };
+        let mut filter = TimeFilter::new(range);
+
+        assert!(!filter.accepts(parser.parse("2020-01-01 10:00:05 INFO Set state to main_menu")));
+        assert!(filter.accepts(parser.parse("2020-01-01 10:00:50 ERROR Failed to start application")));
+        assert!(filter.accepts(parser.parse("An unknown error occurred")));
+        assert!(!filter.accepts(parser.parse("2020-01-01 10:01:00 INFO End of log file")));
+
+        assert_eq!(
+            parse_time_bound("2020-01-01"),
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(0, 0, 0)
+        );
+        assert_eq!(parse_time_bound("yesterday"), None);
+    }
+}